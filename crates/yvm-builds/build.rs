@@ -10,10 +10,15 @@ use yvm::Releases;
 /// Supported values are:
 ///
 /// - "linux-amd64"
+/// - "linux-amd64-musl"
 /// - "linux-aarch64"
+/// - "linux-aarch64-musl"
+/// - "linux-riscv64"
 /// - "macosx-amd64"
 /// - "macosx-aarch64"
 /// - "windows-amd64"
+/// - "windows-aarch64"
+/// - "freebsd-amd64"
 pub const YVM_TARGET_PLATFORM: &str = "YVM_TARGET_PLATFORM";
 
 /// The path to the releases JSON file, that was pre-fetched manually. If this
@@ -21,8 +26,8 @@ pub const YVM_TARGET_PLATFORM: &str = "YVM_TARGET_PLATFORM";
 /// platfrom or perform network calls, and will instead treat the file as the
 /// source of truth.
 ///
-/// Must follow the same format as the upstream `list.json`, e.g.
-/// [this one](https://raw.githubusercontent.com/nikitastupin/ylem/af2fce8988e41753ab4f726e0273ea8244de5dba/linux/aarch64/list.json)
+/// Must follow the format of the release lists bundled with yvm, which `yvm releases generate`
+/// produces and `yvm releases validate` checks.
 pub const YVM_RELEASES_LIST_JSON: &str = "YVM_RELEASES_LIST_JSON";
 
 /// Returns the platform to generate the constants for
@@ -0,0 +1,450 @@
+//! Credentials for downloading from private releases.
+
+#[cfg(feature = "reqwest")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(feature = "reqwest")]
+use http::HeaderValue;
+#[cfg(feature = "reqwest")]
+use serde::Deserialize;
+use std::{env, fmt, fs, path::PathBuf};
+use url::Url;
+
+use crate::{releases, Config, YlemVmError};
+
+const GITHUB_HOST: &str = "github.com";
+const GITHUB_API_HOST: &str = "api.github.com";
+
+/// How secrets are shown in debug output and configuration listings.
+pub(crate) const REDACTED: &str = "***";
+
+/// A token or password, which is never printed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Returns the secret itself.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Whether a credential can be sent in a header. Control characters, e.g. the line break of a
+/// token pasted from a file, can't.
+pub(crate) fn is_valid_credential(credential: &str) -> bool {
+    !credential.chars().any(char::is_control)
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({REDACTED})")
+    }
+}
+
+/// Credentials sent in the `Authorization` header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Credentials {
+    /// A bearer token, e.g. a GitHub token.
+    Bearer(Secret),
+    /// A login and password, e.g. from a netrc file.
+    Basic { login: String, password: Secret },
+}
+
+/// Credentials for the host of the configured releases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Auth {
+    host: String,
+    credentials: Credentials,
+}
+
+impl Auth {
+    /// Creates credentials sent to `host` only.
+    pub fn new(host: impl Into<String>, credentials: Credentials) -> Self {
+        Self {
+            host: host.into(),
+            credentials,
+        }
+    }
+
+    /// Looks up the credentials for the host of [`Config::releases_url`]. The `auth-token`
+    /// configuration key takes precedence over the `GITHUB_TOKEN` environment variable, which is
+    /// only used for releases on github.com, and over the entry of the host in the netrc file
+    /// (`NETRC`, or `.netrc` in the home directory). The `default` entry of the netrc file is only
+    /// used for the host of the default `releases-url`. Fails with
+    /// [`YlemVmError::InvalidCredentials`] if the credentials can't be sent.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, YlemVmError> {
+        let netrc = netrc_path().and_then(|path| fs::read_to_string(path).ok());
+        Self::resolve(config, env::var("GITHUB_TOKEN").ok(), netrc.as_deref())
+    }
+
+    fn resolve(
+        config: &Config,
+        github_token: Option<String>,
+        netrc: Option<&str>,
+    ) -> Result<Option<Self>, YlemVmError> {
+        let Some(host) = config.releases_url.host_str() else {
+            return Ok(None);
+        };
+        let github_token = github_token
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty() && host == GITHUB_HOST);
+        let default_url = Url::parse(releases::YLEM_RELEASES_URL).ok();
+        let is_default = default_url.as_ref().and_then(Url::host_str) == Some(host);
+        let credentials = match (&config.auth_token, github_token) {
+            (Some(token), _) => Credentials::Bearer(token.clone()),
+            (None, Some(token)) if is_valid_credential(&token) => {
+                Credentials::Bearer(Secret(token))
+            }
+            (None, Some(_)) => return Err(invalid_credentials("GITHUB_TOKEN")),
+            (None, None) => {
+                match netrc.and_then(|netrc| netrc_credentials(netrc, host, is_default)) {
+                    Some(Credentials::Basic { login, password })
+                        if !is_valid_credential(&login)
+                            || !is_valid_credential(password.expose()) =>
+                    {
+                        return Err(invalid_credentials(&format!("the netrc entry of {host}")));
+                    }
+                    Some(credentials) => credentials,
+                    None => return Ok(None),
+                }
+            }
+        };
+        Ok(Some(Self::new(host, credentials)))
+    }
+
+    /// Whether the credentials may be sent with a request to `url`. Only the release host gets
+    /// them, and the GitHub API for releases on github.com.
+    pub fn applies_to(&self, url: &Url) -> bool {
+        match url.host_str() {
+            Some(host) => {
+                host == self.host || (self.host == GITHUB_HOST && host == GITHUB_API_HOST)
+            }
+            None => false,
+        }
+    }
+
+    /// Value of the `Authorization` header, marked as sensitive. Fails for credentials that were
+    /// not validated while loading them, e.g. passed to [`Auth::new`].
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn header(&self) -> Result<HeaderValue, YlemVmError> {
+        let value = match &self.credentials {
+            Credentials::Bearer(token) => format!("Bearer {}", token.expose()),
+            Credentials::Basic { login, password } => {
+                let encoded = STANDARD.encode(format!("{login}:{}", password.expose()));
+                format!("Basic {encoded}")
+            }
+        };
+        let mut header = HeaderValue::from_str(&value)
+            .map_err(|_| invalid_credentials(&format!("for {}", self.host)))?;
+        header.set_sensitive(true);
+        Ok(header)
+    }
+}
+
+fn invalid_credentials(origin: &str) -> YlemVmError {
+    YlemVmError::InvalidCredentials(origin.to_string())
+}
+
+fn netrc_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("NETRC") {
+        return Some(path.into());
+    }
+    let name = if cfg!(windows) { "_netrc" } else { ".netrc" };
+    dirs::home_dir().map(|home| home.join(name))
+}
+
+/// Returns the login and password of the `machine` entry for `host` in a netrc file, falling back
+/// to the `default` entry if `use_default`.
+fn netrc_credentials(netrc: &str, host: &str, use_default: bool) -> Option<Credentials> {
+    #[derive(Default)]
+    struct Entry<'a> {
+        machine: Option<&'a str>,
+        login: Option<&'a str>,
+        password: Option<&'a str>,
+    }
+
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut tokens = netrc.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => entries.push(Entry {
+                machine: tokens.next(),
+                ..Default::default()
+            }),
+            "default" => entries.push(Entry::default()),
+            "login" | "password" | "account" => {
+                let value = tokens.next();
+                if let Some(entry) = entries.last_mut() {
+                    match token {
+                        "login" => entry.login = value,
+                        "password" => entry.password = value,
+                        _ => {}
+                    }
+                }
+            }
+            // macro definitions follow the machine entries
+            "macdef" => break,
+            _ => {}
+        }
+    }
+
+    let entry = entries
+        .iter()
+        .find(|entry| entry.machine == Some(host))
+        .or_else(|| {
+            entries
+                .iter()
+                .find(|entry| use_default && entry.machine.is_none())
+        })?;
+    Some(Credentials::Basic {
+        login: entry.login.unwrap_or_default().to_string(),
+        password: Secret::new(entry.password?),
+    })
+}
+
+/// A release asset on github.com. Assets of private repositories can't be downloaded from their
+/// `releases/download` URL with a token, but through the API, which redirects to a short-lived
+/// URL of the file.
+#[cfg(feature = "reqwest")]
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct GithubAsset {
+    owner: String,
+    repo: String,
+    tag: String,
+    name: String,
+}
+
+#[cfg(feature = "reqwest")]
+impl GithubAsset {
+    /// Parses `https://github.com/<owner>/<repo>/releases/download/<tag>/<name>`.
+    pub(crate) fn parse(url: &Url) -> Option<Self> {
+        if url.host_str() != Some(GITHUB_HOST) {
+            return None;
+        }
+        match url.path_segments()?.collect::<Vec<_>>()[..] {
+            [owner, repo, "releases", "download", tag, name] => Some(Self {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                tag: tag.to_string(),
+                name: name.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// API URL of the release, listing its assets.
+    pub(crate) fn release_url(&self) -> Url {
+        let mut url = Url::parse(&format!("https://{GITHUB_API_HOST}")).unwrap();
+        url.path_segments_mut().unwrap().extend([
+            "repos",
+            &self.owner,
+            &self.repo,
+            "releases",
+            "tags",
+            &self.tag,
+        ]);
+        url
+    }
+
+    /// Returns the API URL of the asset from the release returned by [`Self::release_url`].
+    pub(crate) fn asset_url(&self, release: &[u8]) -> Option<Url> {
+        #[derive(Deserialize)]
+        struct Release {
+            assets: Vec<Asset>,
+        }
+        #[derive(Deserialize)]
+        struct Asset {
+            name: String,
+            url: String,
+        }
+
+        let release: Release = serde_json::from_slice(release).ok()?;
+        release
+            .assets
+            .into_iter()
+            .find(|asset| asset.name == self.name)
+            .and_then(|asset| Url::parse(&asset.url).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(releases_url: &str, auth_token: Option<&str>) -> Config {
+        Config {
+            releases_url: Url::parse(releases_url).unwrap(),
+            auth_token: auth_token.map(Secret::new),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resolves_credentials_in_order() {
+        let github = "https://github.com/core-coin/ylem/releases/download";
+        let bearer = |token: &str| Credentials::Bearer(Secret::new(token));
+        let netrc = "machine github.com login me password pass";
+        let resolve = |config: &Config, token: Option<&str>, netrc| {
+            Auth::resolve(config, token.map(String::from), netrc)
+                .unwrap()
+                .map(|auth| auth.credentials)
+        };
+
+        assert_eq!(
+            resolve(
+                &config(github, Some("configured")),
+                Some("env"),
+                Some(netrc)
+            ),
+            Some(bearer("configured"))
+        );
+        assert_eq!(
+            resolve(&config(github, None), Some("env"), Some(netrc)),
+            Some(bearer("env"))
+        );
+        assert_eq!(
+            resolve(&config(github, None), Some(""), Some(netrc)),
+            Some(Credentials::Basic {
+                login: "me".to_string(),
+                password: Secret::new("pass"),
+            })
+        );
+        assert_eq!(resolve(&config(github, None), None, None), None);
+        // the default netrc entry is not sent to mirrors
+        let default = Some("default login me password pass");
+        assert!(resolve(&config(github, None), None, default).is_some());
+        assert_eq!(
+            resolve(&config("https://mirror.example.com", None), None, default),
+            None
+        );
+        // GITHUB_TOKEN is not sent to other hosts
+        assert_eq!(
+            resolve(
+                &config("https://mirror.example.com", None),
+                Some("env"),
+                None
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_credentials_with_control_characters() {
+        let github = config("https://github.com/core-coin/ylem/releases/download", None);
+        // a trailing line break is trimmed
+        let auth = Auth::resolve(&github, Some("token\n".to_string()), None).unwrap();
+        assert_eq!(
+            auth.unwrap().credentials,
+            Credentials::Bearer(Secret::new("token"))
+        );
+
+        let err = Auth::resolve(&github, Some("to\x1bken".to_string()), None).unwrap_err();
+        assert!(matches!(err, YlemVmError::InvalidCredentials(_)));
+        let netrc = "machine github.com login me password pa\x7fss";
+        let err = Auth::resolve(&github, None, Some(netrc)).unwrap_err();
+        assert!(!err.to_string().contains("pa\x7fss"));
+
+        let mut config = Config::default();
+        assert!(config.set("auth-token", "to\rken").is_err());
+        let auth = Auth::new("example.com", Credentials::Bearer(Secret::new("to\nken")));
+        assert!(matches!(
+            auth.header(),
+            Err(YlemVmError::InvalidCredentials(_))
+        ));
+    }
+
+    #[test]
+    fn only_applies_to_release_host() {
+        let auth = Auth::new("github.com", Credentials::Bearer(Secret::new("token")));
+        for (url, applies) in [
+            ("https://github.com/core-coin/ylem", true),
+            ("https://api.github.com/repos/core-coin/ylem", true),
+            ("https://objects.githubusercontent.com/asset", false),
+            ("https://github.com.example.com/asset", false),
+        ] {
+            assert_eq!(auth.applies_to(&Url::parse(url).unwrap()), applies, "{url}");
+        }
+
+        let auth = Auth::new(
+            "mirror.example.com",
+            Credentials::Bearer(Secret::new("token")),
+        );
+        assert!(!auth.applies_to(&Url::parse("https://api.github.com").unwrap()));
+    }
+
+    #[test]
+    fn never_prints_secrets() {
+        let auth = Auth::new(
+            "example.com",
+            Credentials::Basic {
+                login: "me".to_string(),
+                password: Secret::new("hunter2"),
+            },
+        );
+        assert!(!format!("{auth:?}").contains("hunter2"));
+        let header = auth.header().unwrap();
+        assert!(!format!("{header:?}").contains("hunter2"));
+        assert!(header.is_sensitive());
+        assert_eq!(header, "Basic bWU6aHVudGVyMg==");
+
+        let config = config("https://example.com", Some("hunter2"));
+        assert!(!format!("{config:?}").contains("hunter2"));
+        assert_eq!(config.get("auth-token").unwrap(), REDACTED);
+    }
+
+    #[test]
+    fn parses_netrc() {
+        let netrc = "
+            machine example.com
+              login alice
+              password secret1
+            machine github.com login bob password secret2
+            default login anonymous password guest
+            macdef init
+            machine ignored.com login eve password secret3
+        ";
+        let password = |host| match netrc_credentials(netrc, host, true) {
+            Some(Credentials::Basic { password, .. }) => Some(password.expose().to_string()),
+            _ => None,
+        };
+        assert_eq!(password("example.com").as_deref(), Some("secret1"));
+        assert_eq!(password("github.com").as_deref(), Some("secret2"));
+        assert_eq!(password("ignored.com").as_deref(), Some("guest"));
+        assert_eq!(
+            netrc_credentials("machine a.com login x", "a.com", true),
+            None
+        );
+        assert!(netrc_credentials(netrc, "ignored.com", false).is_none());
+    }
+
+    #[test]
+    fn resolves_github_assets() {
+        let url = Url::parse(
+            "https://github.com/core-coin/ylem/releases/download/1.1.2/ylem-linux-x86_64",
+        )
+        .unwrap();
+        let asset = GithubAsset::parse(&url).unwrap();
+        assert_eq!(
+            asset.release_url().as_str(),
+            "https://api.github.com/repos/core-coin/ylem/releases/tags/1.1.2"
+        );
+        let release = br#"{"assets": [
+            {"name": "ylem-darwin-x86_64", "url": "https://api.github.com/assets/1"},
+            {"name": "ylem-linux-x86_64", "url": "https://api.github.com/assets/2"}
+        ]}"#;
+        assert_eq!(
+            asset.asset_url(release).unwrap().as_str(),
+            "https://api.github.com/assets/2"
+        );
+
+        for other in [
+            "https://github.com/core-coin/ylem/releases/tag/1.1.2",
+            "https://mirror.example.com/core-coin/ylem/releases/download/1.1.2/ylem",
+        ] {
+            assert_eq!(GithubAsset::parse(&Url::parse(other).unwrap()), None);
+        }
+    }
+}
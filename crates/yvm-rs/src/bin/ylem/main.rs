@@ -1,13 +1,84 @@
-use std::{env, process::Command};
+use anyhow::Context;
+use std::{env, ffi::OsString, path::Path, process::Command};
 
 fn main() -> anyhow::Result<()> {
-    let args = env::args().skip(1).collect::<Vec<String>>();
+    let mut args = env::args_os();
+    // launchers such as `ylem-1.1.2` are links to the shim that run their version
+    let launched = args
+        .next()
+        .and_then(|argv0| yvm_lib::launcher_version(Path::new(&argv0)));
+    let mut args = args.peekable();
 
-    let version = yvm_lib::current_version()?.ok_or(yvm_lib::YlemVmError::GlobalVersionNotSet)?;
+    // `ylem +<version> ...` runs another version than the active one, like rustup's `+toolchain`
+    let spec = match args.peek().and_then(|arg| arg.to_str()?.strip_prefix('+')) {
+        Some(spec) if launched.is_none() => Some(
+            spec.parse::<yvm_lib::VersionSpec>()
+                .with_context(|| format!("invalid version +{spec}"))?,
+        ),
+        _ => None,
+    };
+    let (version, config) = match (launched, spec) {
+        (Some(version), _) => (version, None),
+        (None, Some(spec)) => {
+            args.next();
+            let config = yvm_lib::Config::load()?;
+            (yvm_lib::resolve_version_spec(&spec, &config)?, Some(config))
+        }
+        (None, None) => {
+            let version =
+                yvm_lib::active_version()?.ok_or(yvm_lib::YlemVmError::GlobalVersionNotSet)?;
+            (version, None)
+        }
+    };
     let mut version_path = yvm_lib::version_path(version.to_string().as_str());
     version_path.push(format!("ylem-{}", version.to_string().as_str()));
 
-    let status = Command::new(version_path).args(args).status()?;
-    let code = status.code().unwrap_or(-1);
-    std::process::exit(code);
+    if !version_path.exists() {
+        let config = match config {
+            Some(config) => config,
+            None => yvm_lib::Config::load()?,
+        };
+        if !config.auto_install {
+            return Err(yvm_lib::YlemVmError::VersionNotInstalled(version.to_string()).into());
+        }
+        eprintln!("Installing Ylem {version}");
+        tokio::runtime::Runtime::new()?.block_on(yvm_lib::install(&version))?;
+    }
+    let _ = yvm_lib::record_last_used(&version);
+
+    run(&version_path, args.collect())
+}
+
+/// Replaces the shim with the compiler, so that it gets signals directly and its parent sees its
+/// exit status, including terminations by signals. The compiler gets the path of its binary as
+/// argv[0], as if it was run directly.
+#[cfg(target_family = "unix")]
+fn run(ylem: &Path, args: Vec<OsString>) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    // only returns if the compiler could not be run
+    let err = Command::new(ylem).args(args).exec();
+    Err(err).with_context(|| format!("could not run {}", ylem.display()))
+}
+
+/// Runs the compiler and exits with its exit code.
+#[cfg(not(target_family = "unix"))]
+fn run(ylem: &Path, args: Vec<OsString>) -> anyhow::Result<()> {
+    // Ctrl-C reaches every process attached to the console, so the shim leaves it to the compiler
+    // and keeps waiting for it to exit
+    ctrlc::set_handler(|| {})?;
+    let status = Command::new(ylem).args(args).status()?;
+    std::process::exit(exit_code(status));
+}
+
+/// Exit code of the compiler, mapping terminations by Ctrl-C to the conventional 128 + SIGINT.
+#[cfg(not(target_family = "unix"))]
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    const STATUS_CONTROL_C_EXIT: i32 = 0xC000013Au32 as i32;
+    const SIGINT: i32 = 2;
+    match status.code() {
+        Some(STATUS_CONTROL_C_EXIT) => 128 + SIGINT,
+        Some(code) => code,
+        None => 1,
+    }
 }
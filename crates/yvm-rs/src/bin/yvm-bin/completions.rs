@@ -0,0 +1,153 @@
+use clap::Command;
+use clap_complete::Shell;
+use std::io::Write;
+
+/// Subcommands whose version arguments are completed through `yvm __complete <subcommand>`.
+const DYNAMIC_SUBCOMMANDS: [&str; 4] = ["install", "use", "remove", "shell"];
+
+const BASH_DYNAMIC: &str = r#"
+_yvm_versions() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ ${COMP_CWORD} -ge 2 && "${cur}" != -* && "${prev}" != -* ]]; then
+        case "${COMP_WORDS[1]}" in
+            install|use|remove|shell)
+                COMPREPLY=( $(compgen -W "$(yvm __complete "${COMP_WORDS[1]}" 2>/dev/null)" -- "${cur}") )
+                return 0
+                ;;
+        esac
+    fi
+    _yvm "$@"
+}
+
+complete -F _yvm_versions -o bashdefault -o default yvm
+"#;
+
+const ZSH_DYNAMIC: &str = r#"(( $+functions[_yvm_versions] )) ||
+_yvm_versions() {
+    local -a versions
+    versions=(${(f)"$(yvm __complete $1 2>/dev/null)"})
+    compadd -V versions -a versions
+}
+
+"#;
+
+/// Replaces `from` in the generated `script`, failing if it does not occur. The dynamic completion
+/// is patched into fragments of clap_complete's output, which may change with its version.
+fn patch(script: &str, from: &str, to: &str) -> anyhow::Result<String> {
+    anyhow::ensure!(
+        script.contains(from),
+        "Generated completion script lacks `{}`",
+        from.trim()
+    );
+    Ok(script.replace(from, to))
+}
+
+/// Writes the completion script for `shell` to `out`. Version arguments of `install`, `use`,
+/// `remove` and `shell` are completed at runtime by calling the hidden `yvm __complete` command.
+pub fn generate(shell: Shell, cmd: &mut Command, out: &mut impl Write) -> anyhow::Result<()> {
+    let mut buf = Vec::new();
+    clap_complete::generate(shell, cmd, "yvm", &mut buf);
+    let mut script = String::from_utf8(buf)?;
+
+    match shell {
+        Shell::Bash => script.push_str(BASH_DYNAMIC),
+        Shell::Zsh => {
+            script = patch(
+                &script,
+                "'*::versions:_default'",
+                "'*::versions:_yvm_versions install'",
+            )?;
+            script = patch(
+                &script,
+                "':version:_default'",
+                "':version:_yvm_versions use'",
+            )?;
+            script = patch(
+                &script,
+                "'::version:_default'",
+                "'::version:_yvm_versions use'",
+            )?;
+            // the helper has to be defined before the generated script invokes `_yvm`
+            let entry = "if [ \"$funcstack[1]\" = \"_yvm\" ]";
+            script = patch(&script, entry, &format!("{ZSH_DYNAMIC}{entry}"))?;
+        }
+        Shell::Fish => {
+            script.push('\n');
+            for subcommand in DYNAMIC_SUBCOMMANDS {
+                script.push_str(&format!(
+                    "complete -c yvm -n \"__fish_seen_subcommand_from {subcommand}\" -f -a \"(yvm __complete {subcommand})\"\n"
+                ));
+            }
+        }
+        Shell::PowerShell => {
+            for subcommand in DYNAMIC_SUBCOMMANDS {
+                let case = format!("'yvm;{subcommand}' {{\n");
+                script = patch(
+                    &script,
+                    &case,
+                    &format!(
+                        "{case}            yvm __complete {subcommand} | ForEach-Object {{ [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_) }}\n"
+                    ),
+                )?;
+            }
+        }
+        _ => {}
+    }
+
+    out.write_all(script.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Opts;
+    use clap::CommandFactory;
+
+    fn script(shell: Shell) -> String {
+        let mut out = Vec::new();
+        generate(shell, &mut Opts::command(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn patches_fail_on_missing_fragments() {
+        assert!(patch("abc", "b", "x").is_ok());
+        assert!(patch("abc", "d", "x").is_err());
+    }
+
+    #[test]
+    fn bash_completes_versions() {
+        let script = script(Shell::Bash);
+        assert!(script.contains("complete -F _yvm_versions"));
+        assert!(script.contains("yvm __complete"));
+    }
+
+    #[test]
+    fn zsh_completes_versions() {
+        let script = script(Shell::Zsh);
+        assert!(!script.contains("version:_default"));
+        assert!(!script.contains("versions:_default"));
+        assert!(script.contains("'*::versions:_yvm_versions install'"));
+        assert!(
+            script.find("_yvm_versions() {").unwrap()
+                < script.find("if [ \"$funcstack[1]\" = \"_yvm\" ]").unwrap()
+        );
+    }
+
+    #[test]
+    fn fish_completes_versions() {
+        let script = script(Shell::Fish);
+        for subcommand in DYNAMIC_SUBCOMMANDS {
+            assert!(script.contains(&format!("(yvm __complete {subcommand})")));
+        }
+    }
+
+    #[test]
+    fn powershell_completes_versions() {
+        let script = script(Shell::PowerShell);
+        for subcommand in DYNAMIC_SUBCOMMANDS {
+            assert!(script.contains(&format!("yvm __complete {subcommand} |")));
+        }
+    }
+}
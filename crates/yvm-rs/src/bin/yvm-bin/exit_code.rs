@@ -0,0 +1,134 @@
+//! Exit codes of `yvm`, so that scripts can tell failures apart. The codes are stable, and
+//! documented in the README.
+
+use yvm_lib::YlemVmError;
+
+/// Why `yvm` failed. Every [`YlemVmError`] maps to one of these, see [`ExitCode::of`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Any failure without a code of its own.
+    Failure = 1,
+    /// Invalid arguments, e.g. a malformed version, or a prompt that can't be answered. Also
+    /// used by clap for arguments it rejects.
+    Usage = 2,
+    /// The version doesn't exist.
+    UnknownVersion = 3,
+    /// The version exists, but has no build for the platform.
+    UnsupportedVersion = 4,
+    /// The version is not installed.
+    VersionNotInstalled = 5,
+    /// No global version is set, or none was set before the current one.
+    GlobalVersionNotSet = 6,
+    /// A download failed.
+    Network = 7,
+    /// A download doesn't match the checksum of its release list, or the list has none.
+    ChecksumMismatch = 8,
+    /// Another yvm kept installing the same version for longer than the `lock-timeout`.
+    Timeout = 9,
+    /// The config is invalid.
+    Config = 10,
+    /// A file system operation failed.
+    Io = 11,
+    /// The data directory was set up by a newer yvm.
+    UnsupportedLayout = 12,
+    /// yvm doesn't support the platform it runs on.
+    UnsupportedPlatform = 13,
+}
+
+impl ExitCode {
+    /// Returns the exit code for an error, from the first of its causes that has one.
+    pub fn of(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(Reported(code)) = cause.downcast_ref() {
+                return *code;
+            }
+            if let Some(err) = cause.downcast_ref::<YlemVmError>() {
+                return Self::of_error(err);
+            }
+            // the library's semver errors are wrapped in `YlemVmError`, so these come from
+            // versions on the command line
+            if cause.is::<UsageError>() || cause.is::<semver::Error>() {
+                return ExitCode::Usage;
+            }
+        }
+        ExitCode::Failure
+    }
+
+    fn of_error(err: &YlemVmError) -> Self {
+        match err {
+            YlemVmError::GlobalVersionNotSet | YlemVmError::NoPreviousGlobalVersion => {
+                ExitCode::GlobalVersionNotSet
+            }
+            YlemVmError::InvalidReleaseTag(_) => ExitCode::Usage,
+            YlemVmError::UnknownVersion | YlemVmError::NoMatchingVersion { .. } => {
+                ExitCode::UnknownVersion
+            }
+            YlemVmError::UnsupportedVersion { .. } => ExitCode::UnsupportedVersion,
+            YlemVmError::VersionNotInstalled(_) => ExitCode::VersionNotInstalled,
+            YlemVmError::ReqwestError(_) | YlemVmError::UnsuccessfulResponse(..) => {
+                ExitCode::Network
+            }
+            YlemVmError::ChecksumMismatch { .. } | YlemVmError::MissingChecksum(_) => {
+                ExitCode::ChecksumMismatch
+            }
+            YlemVmError::Timeout(..) => ExitCode::Timeout,
+            YlemVmError::UnknownConfigKey(_)
+            | YlemVmError::InvalidConfigValue { .. }
+            | YlemVmError::InvalidConfigFile(..)
+            | YlemVmError::InvalidCredentials(_) => ExitCode::Config,
+            YlemVmError::IoError(_) | YlemVmError::Io { .. } => ExitCode::Io,
+            YlemVmError::ZipError(_) => ExitCode::Io,
+            YlemVmError::UnsupportedLayout { .. } => ExitCode::UnsupportedLayout,
+            YlemVmError::UnsupportedOs(_) => ExitCode::UnsupportedPlatform,
+            YlemVmError::CouldNotPatchForNixOs(..)
+            | YlemVmError::SerdeJsonError(_)
+            | YlemVmError::SemverError(_)
+            | YlemVmError::UrlError(_) => ExitCode::Failure,
+        }
+    }
+}
+
+/// An outcome that was already reported to the user, and only needs to exit with its code.
+#[derive(Debug, thiserror::Error)]
+#[error("exit code {}", *.0 as i32)]
+pub struct Reported(pub ExitCode);
+
+/// Invalid use of the command line that clap can't detect, exiting with [`ExitCode::Usage`].
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct UsageError(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_errors_to_exit_codes() {
+        for (err, code) in [
+            (
+                anyhow::Error::new(YlemVmError::UnknownVersion),
+                ExitCode::UnknownVersion,
+            ),
+            (
+                anyhow::Error::new(YlemVmError::Timeout(
+                    "1.1.2".to_string(),
+                    std::time::Duration::from_secs(1),
+                ))
+                .context("installing 1.1.2"),
+                ExitCode::Timeout,
+            ),
+            (
+                anyhow::Error::new(Reported(ExitCode::VersionNotInstalled)),
+                ExitCode::VersionNotInstalled,
+            ),
+            (
+                semver::Version::parse("nope").unwrap_err().into(),
+                ExitCode::Usage,
+            ),
+            (UsageError("pass --yes".to_string()).into(), ExitCode::Usage),
+            (anyhow::anyhow!("something else"), ExitCode::Failure),
+        ] {
+            assert_eq!(ExitCode::of(&err), code, "{err:#}");
+        }
+    }
+}
@@ -1,58 +1,375 @@
-use clap::Parser;
-use dialoguer::Input;
+use anyhow::Context;
+use clap::{CommandFactory, Parser, Subcommand};
 use semver::Version;
 
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+mod completions;
+mod exit_code;
 mod print;
+mod prompt;
+
+use exit_code::{ExitCode, Reported, UsageError};
+use prompt::Prompt;
 
 #[derive(Debug, Parser)]
 #[clap(name = "ylem-vm", about = "Ylem version manager")]
+struct Opts {
+    #[clap(
+        long,
+        short = 'y',
+        global = true,
+        conflicts_with = "no",
+        help = "Answer yes to all prompts"
+    )]
+    yes: bool,
+    #[clap(long, global = true, help = "Answer no to all prompts")]
+    no: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "Platform to use instead of the detected one, e.g. linux-amd64-musl"
+    )]
+    platform: Option<yvm_lib::Platform>,
+    #[clap(subcommand)]
+    command: YlemVm,
+}
+
+#[derive(Debug, Subcommand)]
 enum YlemVm {
     #[clap(about = "List all versions of Ylem")]
     List,
     #[clap(about = "Install Ylem versions")]
-    Install { versions: Vec<String> },
-    #[clap(about = "Use a Ylem version")]
+    Install {
+        versions: Vec<String>,
+        #[clap(
+            long,
+            help = "Data directory to install into instead of the yvm one, e.g. to provision another machine"
+        )]
+        root: Option<PathBuf>,
+    },
+    #[clap(about = "Use a Ylem version, or the previous global version with -")]
     Use { version: String },
     #[clap(about = "Remove a Ylem version")]
     Remove { version: String },
+    #[clap(about = "Show who installed, removed and used versions, and when")]
+    History {
+        #[clap(long, help = "Print the entries as JSON lines")]
+        json: bool,
+    },
+    #[clap(about = "Remove installed versions that are not in use")]
+    Prune {
+        #[clap(help = "Project roots to search for .ylem-version files whose versions are kept")]
+        roots: Vec<PathBuf>,
+        #[clap(
+            long,
+            default_value = "0",
+            help = "Number of the most recent versions to keep"
+        )]
+        keep_latest: usize,
+        #[clap(
+            long,
+            value_parser = humantime::parse_duration,
+            help = "Only remove versions last used longer ago than this, e.g. 30days"
+        )]
+        older_than: Option<Duration>,
+        #[clap(long, help = "Show what would be removed without removing anything")]
+        dry_run: bool,
+    },
+    #[clap(about = "Check the yvm installation for problems")]
+    Doctor {
+        #[clap(long, help = "Fix the problems that can be fixed automatically")]
+        fix: bool,
+        #[clap(long, help = "Remove install locks whose owning process is gone")]
+        fix_locks: bool,
+    },
+    #[clap(about = "Set up the data directory, the ylem shim and the PATH of your shell")]
+    Setup {
+        #[clap(long, help = "Shell to set up, detected from $SHELL by default")]
+        shell: Option<yvm_lib::Shell>,
+        #[clap(
+            long,
+            help = "Set YLEM_VERSION from .ylem-version files when changing directory"
+        )]
+        use_on_cd: bool,
+    },
+    #[clap(about = "Remove all installed versions and everything else yvm created")]
+    Implode,
+    #[clap(about = "Print shell commands that set up the yvm environment")]
+    Env {
+        #[clap(
+            long,
+            help = "Shell to generate commands for, detected from $SHELL by default"
+        )]
+        shell: Option<yvm_lib::Shell>,
+        #[clap(
+            long,
+            help = "Set YLEM_VERSION from .ylem-version files when changing directory"
+        )]
+        use_on_cd: bool,
+    },
+    #[clap(about = "Rebuild the yvm bin directory with the ylem shim and version launchers")]
+    Rehash,
+    #[clap(about = "Print shell commands that use a Ylem version for the current shell session")]
+    Shell {
+        #[clap(required_unless_present = "unset")]
+        version: Option<String>,
+        #[clap(
+            long,
+            help = "Shell to generate commands for, detected from $SHELL by default"
+        )]
+        shell: Option<yvm_lib::Shell>,
+        #[clap(
+            long,
+            help = "Go back to the global version",
+            conflicts_with = "version"
+        )]
+        unset: bool,
+    },
+    #[clap(about = "Get or set configuration values")]
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+    #[clap(about = "Generate and validate release lists, e.g. for a fork of ylem")]
+    Releases {
+        #[clap(subcommand)]
+        command: ReleasesCommand,
+    },
+    #[clap(about = "Print a shell completion script")]
+    Completions { shell: clap_complete::Shell },
+    #[clap(name = "__complete", hide = true)]
+    Complete { command: String },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    #[clap(about = "Print the effective value of a key")]
+    Get { key: String },
+    #[clap(about = "Set a key in the global config file")]
+    Set {
+        key: String,
+        value: String,
+        #[clap(
+            long,
+            help = "Set it in the .yvm.toml file of the current directory instead"
+        )]
+        project: bool,
+    },
+    #[clap(about = "Print all keys and their effective values")]
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum ReleasesCommand {
+    #[clap(about = "Generate release lists of all platforms from ylem binaries")]
+    Generate {
+        #[clap(
+            long,
+            required_unless_present = "tag",
+            conflicts_with = "tag",
+            help = "Directory with the binaries of each version in a subdirectory named after it"
+        )]
+        dir: Option<PathBuf>,
+        #[clap(
+            long,
+            help = "GitHub release to download the binaries of, tagged with its version, e.g. 1.1.2"
+        )]
+        tag: Option<String>,
+        #[clap(
+            long,
+            requires = "tag",
+            help = "GitHub repository of the release, e.g. core-coin/ylem, taken from releases-url by default"
+        )]
+        repo: Option<String>,
+        #[clap(
+            long,
+            help = "Directory of release lists to merge the releases into instead of printing them"
+        )]
+        out: Option<PathBuf>,
+    },
+    #[clap(about = "Check release lists for duplicates, missing checksums and missing builds")]
+    Validate {
+        #[clap(required = true, help = "Release list files, or directories of them")]
+        paths: Vec<PathBuf>,
+    },
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let opt = YlemVm::parse();
+async fn main() {
+    if let Err(err) = run(Opts::parse()).await {
+        if !err.is::<Reported>() {
+            print::error(&err);
+        }
+        std::process::exit(ExitCode::of(&err) as i32);
+    }
+}
+
+async fn run(opts: Opts) -> anyhow::Result<()> {
+    let prompt = Prompt::new(opts.yes, opts.no);
+    let platform = opts.platform.unwrap_or_else(yvm_lib::platform);
 
+    let command = match opts.command {
+        // removing everything has to work even if the data directory was set up by a newer yvm
+        YlemVm::Implode => return handle_implode(prompt),
+        // shells run these on startup and while typing, whatever the state of the data directory
+        // and the config of the current directory
+        YlemVm::Env { shell, use_on_cd } => return handle_env(shell, use_on_cd),
+        YlemVm::Completions { shell } => {
+            return completions::generate(shell, &mut Opts::command(), &mut std::io::stdout());
+        }
+        YlemVm::Complete { command } => {
+            handle_complete(&command, platform);
+            return Ok(());
+        }
+        command => command,
+    };
     yvm_lib::setup_data_dir()?;
 
-    match opt {
+    // the config commands have to work even if the config is invalid, to be able to fix it
+    let command = match command {
+        YlemVm::Config { command } => return handle_config(command),
+        command => command,
+    };
+    let config = yvm_lib::Config::load()?;
+
+    match command {
         YlemVm::List => {
-            handle_list().await?;
+            handle_list(platform, &config).await?;
         }
-        YlemVm::Install { versions } => {
+        YlemVm::Install { versions, root } => {
+            let mut failed = None;
             for v in versions {
-                handle_install(Version::parse(&v)?).await?;
+                let version = Version::parse(&v)?;
+                let res = match &root {
+                    Some(root) => handle_install_into(version, platform, root, &config).await,
+                    None => handle_install(version, platform, prompt, &config).await,
+                };
+                match res {
+                    // the other versions are installed anyway, exiting with the first failure
+                    Err(err) if err.is::<Reported>() => failed = failed.or(Some(err)),
+                    res => res?,
+                }
+            }
+            if let Some(err) = failed {
+                return Err(err);
             }
         }
         YlemVm::Use { version } => {
-            handle_use(Version::parse(&version)?).await?;
+            let version = match version.as_str() {
+                "-" => yvm_lib::previous_global_version()?
+                    .ok_or(yvm_lib::YlemVmError::NoPreviousGlobalVersion)?,
+                version => Version::parse(version)?,
+            };
+            handle_use(version, platform, prompt, &config).await?;
         }
         YlemVm::Remove { version } => match version.as_str() {
             "ALL" | "all" => {
-                for v in yvm_lib::installed_versions().unwrap_or_default() {
-                    yvm_lib::remove_version(&v)?;
+                if prompt.confirm("Remove all installed versions?", false)? {
+                    for v in yvm_lib::installed_versions().unwrap_or_default() {
+                        yvm_lib::remove_version(&v)?;
+                    }
+                    yvm_lib::unset_global_version()?;
                 }
-                yvm_lib::unset_global_version()?;
             }
-            _ => handle_remove(Version::parse(&version)?)?,
+            _ => handle_remove(Version::parse(&version)?, prompt)?,
         },
+        YlemVm::History { json } => {
+            let history = yvm_lib::history()?;
+            if json {
+                for entry in history {
+                    println!("{}", serde_json::to_string(&entry)?);
+                }
+            } else {
+                print::history(&history);
+            }
+        }
+        YlemVm::Prune {
+            roots,
+            keep_latest,
+            older_than,
+            dry_run,
+        } => {
+            let opts = yvm_lib::PruneOptions {
+                roots,
+                keep_latest,
+                older_than,
+                dry_run,
+            };
+            handle_prune(opts, prompt)?;
+        }
+        YlemVm::Doctor { fix, fix_locks } => {
+            handle_doctor(fix, fix_locks, prompt)?;
+        }
+        YlemVm::Setup { shell, use_on_cd } => {
+            handle_setup(shell, use_on_cd, prompt)?;
+        }
+        YlemVm::Rehash => {
+            handle_rehash()?;
+        }
+        YlemVm::Shell {
+            version,
+            shell,
+            unset,
+        } => {
+            let version = match version {
+                Some(v) if !unset => Some(Version::parse(&v)?),
+                _ => None,
+            };
+            handle_shell(version, shell)?;
+        }
+        YlemVm::Releases { command } => {
+            handle_releases(command, &config).await?;
+        }
+        YlemVm::Config { .. }
+        | YlemVm::Implode
+        | YlemVm::Env { .. }
+        | YlemVm::Completions { .. }
+        | YlemVm::Complete { .. } => {
+            unreachable!("handled before loading the config")
+        }
     }
 
     Ok(())
 }
 
-async fn handle_list() -> anyhow::Result<()> {
-    let all_versions = yvm_lib::all_versions().await?;
+/// Versions installable on `platform`, including the ones only available through its emulation
+/// fallback.
+fn all_versions(
+    platform: yvm_lib::Platform,
+    config: &yvm_lib::Config,
+) -> anyhow::Result<Vec<Version>> {
+    let mut versions = yvm_lib::all_releases(platform)?.clone().into_versions();
+    if let Some(fallback) = yvm_lib::fallback_platform(platform, config) {
+        versions.extend(yvm_lib::all_releases(fallback)?.clone().into_versions());
+        versions.sort();
+        versions.dedup();
+    }
+    Ok(versions)
+}
+
+/// Reports a version that can't be installed on `platform`, exiting with
+/// [`ExitCode::UnsupportedVersion`] if other platforms have a build of it.
+fn unsupported_version(
+    version: &Version,
+    platform: yvm_lib::Platform,
+    all_versions: &[Version],
+) -> anyhow::Error {
+    print::unsupported_version(version, platform, all_versions);
+    let code = if yvm_lib::platforms_with_version(version).is_empty() {
+        ExitCode::UnknownVersion
+    } else {
+        ExitCode::UnsupportedVersion
+    };
+    Reported(code).into()
+}
+
+async fn handle_list(platform: yvm_lib::Platform, config: &yvm_lib::Config) -> anyhow::Result<()> {
+    let all_versions = all_versions(platform, config)?;
     let installed_versions = yvm_lib::installed_versions().unwrap_or_default();
     let current_version = yvm_lib::current_version()?;
 
@@ -64,45 +381,87 @@ async fn handle_list() -> anyhow::Result<()> {
     available_versions.sort();
 
     print::current_version(current_version);
+    if let yvm_lib::CurrentLink::Dangling(target) = yvm_lib::current_link()? {
+        print::dangling_current_link(&target);
+    }
     print::installed_versions(installed_versions);
     print::available_versions(available_versions);
 
     Ok(())
 }
 
-async fn handle_install(version: Version) -> anyhow::Result<()> {
-    let all_versions = yvm_lib::all_versions().await?;
+async fn handle_install(
+    version: Version,
+    platform: yvm_lib::Platform,
+    prompt: Prompt,
+    config: &yvm_lib::Config,
+) -> anyhow::Result<()> {
+    // the data directory only holds builds the shim can run
+    let host = yvm_lib::platform();
+    if !platform.runs_on(host) {
+        return Err(UsageError(format!(
+            "Builds for {platform} can't run on this {host} machine, pass --root <dir> to install them into a separate directory"
+        ))
+        .into());
+    }
+    let all_versions = all_versions(platform, config)?;
     let installed_versions = yvm_lib::installed_versions().unwrap_or_default();
     let current_version = yvm_lib::current_version()?;
 
     if installed_versions.contains(&version) {
         println!("Ylem {version} is already installed");
-        let input: String = Input::new()
-            .with_prompt("Would you like to set it as the global version?")
-            .with_initial_text("Y")
-            .default("N".into())
-            .interact_text()?;
-        if matches!(input.as_str(), "y" | "Y" | "yes" | "Yes") {
+        if prompt.confirm_optional("Would you like to set it as the global version?", false)? {
             yvm_lib::use_version(&version)?;
             print::set_global_version(&version);
         }
     } else if all_versions.contains(&version) {
         let spinner = print::installing_version(&version);
-        yvm_lib::install(&version).await?;
+        yvm_lib::install_with_config(config, platform, &version, &yvm_lib::YVM_DATA_DIR).await?;
         spinner.finish_with_message(format!("Downloaded Ylem: {version}"));
+        if let Some(metadata) = yvm_lib::install_metadata(&version)? {
+            if metadata.platform != platform {
+                print::emulated_build(&version, metadata.platform);
+            }
+        }
         if current_version.is_none() {
             yvm_lib::use_version(&version)?;
             print::set_global_version(&version);
         }
     } else {
-        print::unsupported_version(&version);
+        return Err(unsupported_version(&version, platform, &all_versions));
     }
 
     Ok(())
 }
 
-async fn handle_use(version: Version) -> anyhow::Result<()> {
-    let all_versions = yvm_lib::all_versions().await?;
+async fn handle_install_into(
+    version: Version,
+    platform: yvm_lib::Platform,
+    root: &Path,
+    config: &yvm_lib::Config,
+) -> anyhow::Result<()> {
+    let all_versions = all_versions(platform, config)?;
+    if !all_versions.contains(&version) {
+        return Err(unsupported_version(&version, platform, &all_versions));
+    }
+
+    let spinner = print::installing_version(&version);
+    let path = yvm_lib::install_with_config(config, platform, &version, root).await?;
+    spinner.finish_with_message(format!(
+        "Downloaded Ylem {version} for {platform}: {}",
+        path.display()
+    ));
+
+    Ok(())
+}
+
+async fn handle_use(
+    version: Version,
+    platform: yvm_lib::Platform,
+    prompt: Prompt,
+    config: &yvm_lib::Config,
+) -> anyhow::Result<()> {
+    let all_versions = all_versions(platform, config)?;
     let installed_versions = yvm_lib::installed_versions().unwrap_or_default();
 
     if installed_versions.contains(&version) {
@@ -110,32 +469,22 @@ async fn handle_use(version: Version) -> anyhow::Result<()> {
         print::set_global_version(&version);
     } else if all_versions.contains(&version) {
         println!("Ylem {version} is not installed");
-        let input: String = Input::new()
-            .with_prompt("Would you like to install it?")
-            .with_initial_text("Y")
-            .default("N".into())
-            .interact_text()?;
-        if matches!(input.as_str(), "y" | "Y" | "yes" | "Yes") {
-            handle_install(version).await?;
+        if prompt.confirm("Would you like to install it?", true)? {
+            handle_install(version, platform, prompt, config).await?;
         }
     } else {
-        print::unsupported_version(&version);
+        return Err(unsupported_version(&version, platform, &all_versions));
     }
 
     Ok(())
 }
 
-fn handle_remove(version: Version) -> anyhow::Result<()> {
+fn handle_remove(version: Version, prompt: Prompt) -> anyhow::Result<()> {
     let mut installed_versions = yvm_lib::installed_versions().unwrap_or_default();
     let current_version = yvm_lib::current_version()?;
 
     if installed_versions.contains(&version) {
-        let input: String = Input::new()
-            .with_prompt("Are you sure?")
-            .with_initial_text("Y")
-            .default("N".into())
-            .interact_text()?;
-        if matches!(input.as_str(), "y" | "Y" | "yes" | "Yes") {
+        if prompt.confirm(&format!("Remove Ylem {version}?"), false)? {
             yvm_lib::remove_version(&version)?;
             if let Some(v) = current_version {
                 if version == v {
@@ -153,7 +502,303 @@ fn handle_remove(version: Version) -> anyhow::Result<()> {
         }
     } else {
         print::version_not_found(&version);
+        return Err(Reported(ExitCode::VersionNotInstalled).into());
+    }
+
+    Ok(())
+}
+
+fn handle_prune(opts: yvm_lib::PruneOptions, prompt: Prompt) -> anyhow::Result<()> {
+    let candidates = yvm_lib::prune(&yvm_lib::PruneOptions {
+        dry_run: true,
+        ..opts.clone()
+    })?;
+    print::unreadable_dirs(&candidates.unreadable);
+    let candidates = candidates.versions;
+    if candidates.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+
+    print::pruned_versions(&candidates, true);
+    if opts.dry_run || !prompt.confirm("Remove these versions?", false)? {
+        return Ok(());
+    }
+
+    let pruned = yvm_lib::prune(&opts)?;
+    print::pruned_versions(&pruned.versions, false);
+    Ok(())
+}
+
+fn handle_doctor(fix: bool, fix_locks: bool, prompt: Prompt) -> anyhow::Result<()> {
+    if fix_locks {
+        for path in yvm_lib::remove_stale_locks()? {
+            println!("Removed stale lock {}", path.display());
+        }
+    }
+
+    let findings = yvm_lib::diagnose()?;
+    print::findings(&findings);
+
+    let fixes = findings
+        .iter()
+        .filter_map(|f| f.fix.as_ref())
+        .collect::<Vec<_>>();
+    if fix
+        && !fixes.is_empty()
+        && prompt.confirm(&format!("Apply {} fixes?", fixes.len()), false)?
+    {
+        for f in fixes {
+            yvm_lib::apply_fix(f)?;
+            print::applied_fix(f);
+        }
+    }
+    Ok(())
+}
+
+fn handle_config(command: ConfigCommand) -> anyhow::Result<()> {
+    match command {
+        ConfigCommand::Get { key } => println!("{}", yvm_lib::Config::load()?.get(&key)?),
+        ConfigCommand::Set {
+            key,
+            value,
+            project,
+        } => {
+            let path = if project {
+                std::env::current_dir()?.join(yvm_lib::PROJECT_CONFIG_FILE_NAME)
+            } else {
+                yvm_lib::config_path()
+            };
+            yvm_lib::Config::set_in_file(&path, &key, &value)?;
+            print::config_set(&key, &path);
+        }
+        ConfigCommand::List => print::config(&yvm_lib::Config::load()?),
+    }
+    Ok(())
+}
+
+async fn handle_releases(command: ReleasesCommand, config: &yvm_lib::Config) -> anyhow::Result<()> {
+    match command {
+        ReleasesCommand::Generate {
+            dir,
+            tag,
+            repo,
+            out,
+        } => {
+            let generated = match (dir, tag) {
+                (Some(dir), _) => yvm_lib::releases_from_dir(&dir)?,
+                (None, Some(tag)) => {
+                    let repo = match repo.or_else(|| yvm_lib::github_repo(&config.releases_url)) {
+                        Some(repo) => repo,
+                        None => {
+                            return Err(UsageError(
+                                "releases-url is not a GitHub repository, please pass --repo"
+                                    .to_string(),
+                            )
+                            .into())
+                        }
+                    };
+                    // authenticate like installs from the repository would
+                    let config = yvm_lib::Config {
+                        releases_url: yvm_lib::github_releases_url(&repo)?,
+                        ..config.clone()
+                    };
+                    let downloader = yvm_lib::ReqwestDownloader::for_config(&config)?;
+                    yvm_lib::releases_from_github(&downloader, &config, &repo, &tag).await?
+                }
+                (None, None) => unreachable!("clap requires --dir or --tag"),
+            };
+            print::skipped_artifacts(&generated.skipped);
+
+            let Some(out) = out else {
+                print!("{}", yvm_lib::format_releases(&generated.releases)?);
+                return Ok(());
+            };
+            std::fs::create_dir_all(&out)
+                .with_context(|| format!("Could not create directory {}", out.display()))?;
+            for (platform, releases) in generated.releases {
+                let path = out.join(yvm_lib::list_file_name(platform).expect("supported platform"));
+                let mut list = match std::fs::read(&path) {
+                    Ok(json) => serde_json::from_slice(&json)?,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                        yvm_lib::Releases::default()
+                    }
+                    Err(err) => {
+                        return Err(err)
+                            .with_context(|| format!("Could not read {}", path.display()))
+                    }
+                };
+                yvm_lib::merge_releases(&mut list, &releases);
+                std::fs::write(&path, yvm_lib::format_releases(&list)?)
+                    .with_context(|| format!("Could not write {}", path.display()))?;
+            }
+            print::release_lists_written(&out);
+        }
+        ReleasesCommand::Validate { paths } => {
+            let mut files = Vec::new();
+            for path in paths {
+                if path.is_dir() {
+                    let mut lists: Vec<PathBuf> = std::fs::read_dir(&path)
+                        .and_then(|entries| {
+                            entries
+                                .map(|entry| entry.map(|entry| entry.path()))
+                                .collect()
+                        })
+                        .with_context(|| format!("Could not read directory {}", path.display()))?;
+                    lists.retain(|list| list.extension().map_or(false, |ext| ext == "json"));
+                    lists.sort();
+                    files.extend(lists);
+                } else {
+                    files.push(path);
+                }
+            }
+
+            let mut count = 0;
+            for file in files {
+                let platform = file
+                    .file_name()
+                    .and_then(|name| yvm_lib::list_platform(&name.to_string_lossy()));
+                let json = std::fs::read(&file)
+                    .with_context(|| format!("Could not read {}", file.display()))?;
+                let problems = yvm_lib::validate_releases(&json, platform)?;
+                print::list_problems(&file, &problems);
+                count += problems.len();
+            }
+            if count > 0 {
+                anyhow::bail!("found {count} problems in the release lists");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn detect_shell(shell: Option<yvm_lib::Shell>) -> anyhow::Result<yvm_lib::Shell> {
+    let shell = shell.or_else(yvm_lib::Shell::detect).ok_or_else(|| {
+        UsageError("Could not detect your shell, please pass --shell".to_string())
+    })?;
+    Ok(shell)
+}
+
+fn handle_env(shell: Option<yvm_lib::Shell>, use_on_cd: bool) -> anyhow::Result<()> {
+    let shell = detect_shell(shell)?;
+    link_shim()?;
+    print!("{}", shell.env_script(&yvm_lib::bin_path(), use_on_cd));
+    Ok(())
+}
+
+/// Links the `ylem` shim installed next to this binary into the managed bin directory, returning
+/// its path there if there is one.
+fn link_shim() -> anyhow::Result<Option<PathBuf>> {
+    let shim =
+        std::env::current_exe()?.with_file_name(format!("ylem{}", std::env::consts::EXE_SUFFIX));
+    if !shim.exists() {
+        return Ok(None);
+    }
+    Ok(Some(yvm_lib::install_shim(&shim)?))
+}
+
+fn handle_setup(
+    shell: Option<yvm_lib::Shell>,
+    use_on_cd: bool,
+    prompt: Prompt,
+) -> anyhow::Result<()> {
+    let data_dir = yvm_lib::setup_data_dir()?;
+    let shim = link_shim()?;
+    yvm_lib::rehash()?;
+    print::data_dir_set_up(&data_dir, shim.as_deref());
+
+    let shell = detect_shell(shell)?;
+    let home =
+        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not detect your home directory"))?;
+    let rc_file = shell.rc_file(&home);
+    let line = shell.setup_line(&std::env::current_exe()?, use_on_cd);
+    print::setup_line(&rc_file, &line);
+    if prompt.confirm(&format!("Add it to {}?", rc_file.display()), true)? {
+        if yvm_lib::add_setup_line(&rc_file, &line)? {
+            println!(
+                "Updated {}, restart your shell to apply it",
+                rc_file.display()
+            );
+        } else {
+            println!("{} is already set up", rc_file.display());
+        }
+    }
+
+    for ylem in yvm_lib::ylems_before_shim(std::env::var_os("PATH"), &yvm_lib::bin_path()) {
+        print::ylem_before_shim(&ylem);
+    }
+    Ok(())
+}
+
+fn handle_implode(prompt: Prompt) -> anyhow::Result<()> {
+    let data_dir = yvm_lib::YVM_DATA_DIR.as_path();
+    let question = format!(
+        "Remove {} with all installed versions, and the yvm setup of your shells?",
+        data_dir.display()
+    );
+    if !prompt.confirm(&question, false)? {
+        return Ok(());
+    }
+
+    // the cache directory is configured by the user and may be shared, so it is kept
+    let cache_dir = yvm_lib::Config::load()
+        .ok()
+        .and_then(|config| config.cache_dir);
+    yvm_lib::implode()?;
+    println!("Removed {}", data_dir.display());
+    if let Some(home) = dirs::home_dir() {
+        for shell in [
+            yvm_lib::Shell::Bash,
+            yvm_lib::Shell::Zsh,
+            yvm_lib::Shell::Fish,
+        ] {
+            let rc_file = shell.rc_file(&home);
+            if yvm_lib::remove_setup_line(&rc_file)? {
+                println!("Removed the yvm setup from {}", rc_file.display());
+            }
+        }
     }
+    if let Some(cache_dir) = cache_dir {
+        println!("Kept the artifact cache {}", cache_dir.display());
+    }
+    Ok(())
+}
 
+fn handle_rehash() -> anyhow::Result<()> {
+    let shim = link_shim()?;
+    let launchers = yvm_lib::rehash()?;
+    print::rehashed(shim.as_deref(), &launchers);
     Ok(())
 }
+
+fn handle_shell(version: Option<Version>, shell: Option<yvm_lib::Shell>) -> anyhow::Result<()> {
+    let shell = detect_shell(shell)?;
+
+    match version {
+        Some(version) => {
+            let installed_versions = yvm_lib::installed_versions().unwrap_or_default();
+            if !installed_versions.contains(&version) {
+                return Err(yvm_lib::YlemVmError::VersionNotInstalled(version.to_string()).into());
+            }
+            print!("{}", shell.use_version_script(&version));
+        }
+        None => print!("{}", shell.unset_version_script()),
+    }
+    Ok(())
+}
+
+/// Prints the versions to complete for `command`. Errors are ignored, completing nothing, as they
+/// would be printed in the middle of the command line being typed.
+fn handle_complete(command: &str, platform: yvm_lib::Platform) {
+    let versions = match command {
+        "install" => yvm_lib::Config::load()
+            .ok()
+            .and_then(|config| all_versions(platform, &config).ok())
+            .unwrap_or_default(),
+        "use" | "remove" | "shell" => yvm_lib::installed_versions().unwrap_or_default(),
+        _ => vec![],
+    };
+    for v in versions.iter().rev() {
+        println!("{v}");
+    }
+}
@@ -1,9 +1,23 @@
 use console::style;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use semver::Version;
 use std::time::Duration;
 
+/// Prints an error with its causes on one line, e.g. the reason a file could not be written.
+pub fn error(err: &anyhow::Error) {
+    eprintln!("{} {err:#}", style("error:").red().bold());
+    if let Some(yvm_lib::YlemVmError::Io { path, source, .. }) = err.downcast_ref() {
+        if source.kind() == std::io::ErrorKind::PermissionDenied {
+            eprintln!(
+                "  hint: check the permissions of {}, or of the yvm data directory {}",
+                path.display(),
+                yvm_lib::YVM_DATA_DIR.display()
+            );
+        }
+    }
+}
+
 pub fn current_version(version: Option<Version>) {
     match version {
         Some(v) => {
@@ -15,6 +29,15 @@ pub fn current_version(version: Option<Version>) {
     }
 }
 
+pub fn dangling_current_link(target: &std::path::Path) {
+    let message = format!(
+        "{} points to {}, which is not installed, run `yvm doctor --fix`",
+        yvm_lib::current_path().display(),
+        target.display()
+    );
+    println!("{}", style(message).yellow());
+}
+
 pub fn installed_versions(versions: Vec<Version>) {
     println!("\n{}", style("Installed Versions").bold());
     versions.iter().for_each(|v| {
@@ -54,8 +77,34 @@ pub fn installing_version(version: &Version) -> ProgressBar {
     spinner
 }
 
-pub fn unsupported_version(version: &Version) {
-    println!("{}", style(format!("Version: {version} unsupported")).red());
+/// Prints that a version can't be installed on `platform`, with the platforms that have a build
+/// of it and the closest of the `available` versions.
+pub fn unsupported_version(version: &Version, platform: yvm_lib::Platform, available: &[Version]) {
+    println!(
+        "{}",
+        style(format!("Version: {version} unsupported on {platform}")).red()
+    );
+    let platforms = yvm_lib::platforms_with_version(version);
+    if !platforms.is_empty() {
+        println!("Available for: {}", platforms.iter().join(", "));
+    }
+    let closest = yvm_lib::closest_versions(version, available, 3);
+    if !closest.is_empty() {
+        println!(
+            "Closest versions for {platform}: {}",
+            closest.iter().join(", ")
+        );
+    }
+}
+
+pub fn emulated_build(version: &Version, platform: yvm_lib::Platform) {
+    println!(
+        "{}",
+        style(format!(
+            "No native build of Ylem {version} exists, installed the {platform} build which runs under emulation"
+        ))
+        .yellow()
+    );
 }
 
 pub fn set_global_version(version: &Version) {
@@ -65,3 +114,164 @@ pub fn set_global_version(version: &Version) {
 pub fn version_not_found(version: &Version) {
     println!("{}", style(format!("Version: {version} not found")).red());
 }
+
+pub fn history(history: &[yvm_lib::HistoryEntry]) {
+    if history.is_empty() {
+        println!("No history");
+        return;
+    }
+    for entry in history {
+        let time = std::time::UNIX_EPOCH + Duration::from_secs(entry.timestamp);
+        let mut details = entry
+            .version
+            .as_ref()
+            .map(|v| style(v.to_string()).yellow().to_string())
+            .unwrap_or_default();
+        if let Some(previous) = &entry.previous {
+            details = format!("{details} (was {previous})")
+                .trim_start()
+                .to_string();
+        }
+        if let (Some(url), Some(checksum)) = (&entry.url, &entry.checksum) {
+            details = format!("{details} from {url} (sha256 {checksum})");
+        }
+        println!(
+            "{}  {}  {:<7}  {details}",
+            humantime::format_rfc3339_seconds(time),
+            entry.user,
+            entry.operation.to_string()
+        );
+    }
+}
+
+pub fn unreadable_dirs(errors: &[yvm_lib::YlemVmError]) {
+    for err in errors {
+        let mut message = err.to_string();
+        if let Some(source) = std::error::Error::source(err) {
+            message = format!("{message}: {source}");
+        }
+        let message = format!("{message}, skipped it; versions pinned there are not kept");
+        eprintln!("{}", style(message).yellow());
+    }
+}
+
+pub fn pruned_versions(pruned: &[yvm_lib::PrunedVersion], dry_run: bool) {
+    let (title, action) = if dry_run {
+        ("Versions to Remove", "Would free")
+    } else {
+        ("Removed Versions", "Freed")
+    };
+    println!("\n{}", style(title).bold());
+    pruned.iter().for_each(|p| {
+        println!(
+            "{} ({})",
+            style(p.version.to_string().as_str()).yellow(),
+            HumanBytes(p.size)
+        );
+    });
+    let total = pruned.iter().map(|p| p.size).sum();
+    println!("{action} {}", HumanBytes(total));
+}
+
+pub fn findings(findings: &[yvm_lib::Finding]) {
+    if findings.is_empty() {
+        println!("{}", style("No problems found").green());
+        return;
+    }
+    for finding in findings {
+        let severity = match finding.severity {
+            yvm_lib::Severity::Warning => style("warning").yellow(),
+            yvm_lib::Severity::Error => style("error").red(),
+        };
+        println!("{severity}: {}", finding.message);
+        if let Some(hint) = &finding.hint {
+            println!("  hint: {hint}");
+        }
+        if finding.fix.is_some() {
+            println!("  fixable with `yvm doctor --fix`");
+        }
+    }
+}
+
+pub fn applied_fix(fix: &yvm_lib::Fix) {
+    let message = match fix {
+        yvm_lib::Fix::Remove(path) => format!("Removed {}", path.display()),
+        yvm_lib::Fix::UseVersion(version) => format!("Global version set: {version}"),
+        yvm_lib::Fix::UnsetGlobalVersion => "Global version unset".to_string(),
+        yvm_lib::Fix::MakeExecutable(path) => format!("Made {} executable", path.display()),
+        yvm_lib::Fix::RemoveLock(path) => format!("Removed stale lock {}", path.display()),
+    };
+    println!("{}", style(message).green());
+}
+
+pub fn config(config: &yvm_lib::Config) {
+    for (key, value) in config.list() {
+        println!("{} = {value}", style(key).cyan());
+    }
+}
+
+pub fn config_set(key: &str, path: &std::path::Path) {
+    println!("Set {key} in {}", path.display());
+}
+
+pub fn skipped_artifacts(artifacts: &[String]) {
+    for artifact in artifacts {
+        let message =
+            format!("Skipped {artifact}: not a ylem binary, or another one of its platform");
+        eprintln!("{}", style(message).yellow());
+    }
+}
+
+pub fn release_lists_written(dir: &std::path::Path) {
+    println!("Updated the release lists in {}", dir.display());
+}
+
+pub fn list_problems(path: &std::path::Path, problems: &[yvm_lib::ListProblem]) {
+    if problems.is_empty() {
+        println!("{}: {}", path.display(), style("ok").green());
+        return;
+    }
+    for problem in problems {
+        println!("{}: {} {problem}", path.display(), style("error:").red());
+    }
+}
+
+pub fn data_dir_set_up(data_dir: &std::path::Path, shim: Option<&std::path::Path>) {
+    println!("Set up {}", data_dir.display());
+    match shim {
+        Some(shim) => println!("Linked the ylem shim: {}", shim.display()),
+        None => println!(
+            "{}",
+            style("No ylem shim found next to yvm, reinstall yvm with the ylem feature").yellow()
+        ),
+    }
+}
+
+pub fn setup_line(rc_file: &std::path::Path, line: &str) {
+    println!(
+        "\nTo put the ylem shim on PATH, {} needs this line:\n\n    {line}\n",
+        rc_file.display()
+    );
+}
+
+pub fn ylem_before_shim(ylem: &std::path::Path) {
+    let message = format!(
+        "{} comes before the yvm shim on PATH and is run instead of it, put {} first",
+        ylem.display(),
+        yvm_lib::bin_path().display()
+    );
+    println!("{}", style(message).yellow());
+}
+
+pub fn rehashed(shim: Option<&std::path::Path>, launchers: &[std::path::PathBuf]) {
+    match shim {
+        Some(shim) => println!("Linked the ylem shim: {}", shim.display()),
+        None => println!(
+            "{}",
+            style("No ylem shim found next to yvm, only linked the launchers").yellow()
+        ),
+    }
+    for launcher in launchers {
+        println!("Linked {}", launcher.display());
+    }
+}
@@ -0,0 +1,108 @@
+use dialoguer::Confirm;
+use std::ffi::OsString;
+
+use crate::exit_code::UsageError;
+
+/// Environment variable that disables interactive prompts, as if no terminal was attached.
+pub const YVM_NONINTERACTIVE: &str = "YVM_NONINTERACTIVE";
+
+/// Answers yes/no confirmations, either from the `--yes`/`--no` flags or by asking the user.
+#[derive(Clone, Copy, Debug)]
+pub struct Prompt {
+    answer: Option<bool>,
+}
+
+impl Prompt {
+    pub fn new(yes: bool, no: bool) -> Self {
+        let answer = match (yes, no) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        };
+        Self { answer }
+    }
+
+    /// Asks the user to confirm, using `default` if they just press Enter. Fails instead of
+    /// blocking when no terminal is attached and no answer was given on the command line.
+    pub fn confirm(&self, prompt: &str, default: bool) -> anyhow::Result<bool> {
+        self.confirm_with_env(prompt, default, |name| std::env::var_os(name))
+    }
+
+    /// Like [`confirm`](Self::confirm) for questions whose answer doesn't change what the command
+    /// is asked to do, which take `default` instead of failing when no terminal is attached.
+    pub fn confirm_optional(&self, prompt: &str, default: bool) -> anyhow::Result<bool> {
+        self.confirm_optional_with_env(prompt, default, |name| std::env::var_os(name))
+    }
+
+    fn confirm_optional_with_env(
+        &self,
+        prompt: &str,
+        default: bool,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> anyhow::Result<bool> {
+        if self.answer.is_none() && !is_interactive(&env) {
+            return Ok(default);
+        }
+        self.confirm_with_env(prompt, default, env)
+    }
+
+    fn confirm_with_env(
+        &self,
+        prompt: &str,
+        default: bool,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> anyhow::Result<bool> {
+        if let Some(answer) = self.answer {
+            return Ok(answer);
+        }
+        if !is_interactive(&env) {
+            return Err(UsageError(format!(
+                "Cannot ask \"{prompt}\" in non-interactive mode, pass --yes or --no to answer it"
+            ))
+            .into());
+        }
+        Ok(Confirm::new()
+            .with_prompt(prompt)
+            .default(default)
+            .interact()?)
+    }
+}
+
+fn is_interactive(env: &impl Fn(&str) -> Option<OsString>) -> bool {
+    let disabled = env(YVM_NONINTERACTIVE).map_or(false, |v| !v.is_empty() && v != "0");
+    !disabled && console::user_attended_stderr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_from_flags() {
+        assert!(Prompt::new(true, false)
+            .confirm("Are you sure?", false)
+            .unwrap());
+        assert!(!Prompt::new(false, true)
+            .confirm("Are you sure?", true)
+            .unwrap());
+    }
+
+    #[test]
+    fn fails_fast_when_non_interactive() {
+        let env = |name: &str| (name == YVM_NONINTERACTIVE).then(|| OsString::from("1"));
+        let err = Prompt::new(false, false)
+            .confirm_with_env("Are you sure?", false, env)
+            .unwrap_err();
+        assert!(err.to_string().contains("--yes or --no"));
+
+        for default in [false, true] {
+            let answer = Prompt::new(false, false)
+                .confirm_optional_with_env("Are you sure?", default, env)
+                .unwrap();
+            assert_eq!(answer, default);
+        }
+        assert!(Prompt::new(true, false)
+            .confirm_optional_with_env("Are you sure?", false, env)
+            .unwrap());
+    }
+}
@@ -0,0 +1,228 @@
+//! Blocking versions of the networking parts of the library, for callers without an async
+//! runtime such as build scripts. They share their implementation with the async versions and
+//! only differ in how they download.
+
+use semver::Version;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use url::Url;
+
+#[cfg(feature = "blocking")]
+use crate::{installed_versions, use_version, BlockingReqwestDownloader};
+use crate::{
+    platform, releases, BlockingDownloader, Config, PendingInstall, Platform, Releases,
+    YlemVmError, YVM_DATA_DIR,
+};
+
+/// Blocking version of [`crate::all_versions`]
+pub fn blocking_all_versions() -> Result<Vec<Version>, YlemVmError> {
+    Ok(releases::all_releases(platform::platform())?
+        .clone()
+        .into_versions())
+}
+
+/// Blocking version of [`crate::install`]
+#[cfg(feature = "blocking")]
+pub fn blocking_install(version: &Version) -> Result<PathBuf, YlemVmError> {
+    blocking_install_for(platform::platform(), version, &YVM_DATA_DIR)
+}
+
+/// Blocking version of [`crate::install_for`]
+#[cfg(feature = "blocking")]
+pub fn blocking_install_for(
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    blocking_install_with_config(&Config::load_global()?, platform, version, root)
+}
+
+/// Blocking version of [`crate::install_with_config`]
+#[cfg(feature = "blocking")]
+pub fn blocking_install_with_config(
+    config: &Config,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    let downloader = BlockingReqwestDownloader::for_config(config)?;
+    blocking_install_with_downloader(&downloader, config, platform, version, root)
+}
+
+/// Blocking version of [`crate::install_with_downloader`]
+pub fn blocking_install_with_downloader(
+    downloader: &dyn BlockingDownloader,
+    config: &Config,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    let pending = PendingInstall::resolve(config, platform, version, root)?;
+    blocking_install_pending(downloader, config, pending)
+}
+
+/// Blocking version of [`crate::install_release`]
+#[cfg(feature = "blocking")]
+pub fn blocking_install_release(
+    config: &Config,
+    releases: &Releases,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    let downloader = BlockingReqwestDownloader::for_config(config)?;
+    blocking_install_release_with_downloader(&downloader, config, releases, platform, version, root)
+}
+
+/// Blocking version of [`crate::install_release_with_downloader`]
+pub fn blocking_install_release_with_downloader(
+    downloader: &dyn BlockingDownloader,
+    config: &Config,
+    releases: &Releases,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    let pending = PendingInstall::new(config, releases, platform, version, root)?;
+    blocking_install_pending(downloader, config, pending)
+}
+
+fn blocking_install_pending(
+    downloader: &dyn BlockingDownloader,
+    config: &Config,
+    pending: PendingInstall,
+) -> Result<PathBuf, YlemVmError> {
+    let binbytes = match pending.cached(config) {
+        Some(binbytes) => binbytes,
+        None => {
+            let binbytes = downloader.download(&pending.url, config.request_timeout)?;
+            pending.downloaded(config, binbytes)?
+        }
+    };
+    pending.finish(config, binbytes)
+}
+
+/// Blocking version of [`crate::install_and_use`]
+#[cfg(feature = "blocking")]
+pub fn blocking_use(version: &Version) -> Result<(), YlemVmError> {
+    if !installed_versions()?.contains(version) {
+        blocking_install(version)?;
+    }
+    use_version(version)
+}
+
+/// Synchronous entry point bundling the configuration, target platform and data directory, so
+/// they don't have to be passed to every call.
+///
+/// ```no_run
+/// # #[cfg(feature = "blocking")]
+/// # {
+/// let yvm = yvm_lib::Yvm::new()?;
+/// let ylem = yvm.install(&"1.1.2".parse()?)?;
+/// # }
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct Yvm {
+    config: Config,
+    platform: Platform,
+    root: PathBuf,
+    downloader: Arc<dyn BlockingDownloader>,
+}
+
+impl Yvm {
+    /// Creates a facade for the current machine, loading the global configuration, see
+    /// [`Config::load_global`].
+    #[cfg(feature = "blocking")]
+    pub fn new() -> Result<Self, YlemVmError> {
+        Self::with_config(Config::load_global()?)
+    }
+
+    /// Creates a facade for the current machine using the given configuration. Fails if the
+    /// credentials of the release host are invalid, see [`crate::Auth::from_config`].
+    #[cfg(feature = "blocking")]
+    pub fn with_config(config: Config) -> Result<Self, YlemVmError> {
+        let downloader = BlockingReqwestDownloader::for_config(&config)?;
+        Ok(Self::with_downloader(config, downloader))
+    }
+
+    /// Creates a facade for the current machine using the given configuration and
+    /// [`BlockingDownloader`].
+    pub fn with_downloader(config: Config, downloader: impl BlockingDownloader + 'static) -> Self {
+        Self {
+            config,
+            platform: platform::platform(),
+            root: YVM_DATA_DIR.to_path_buf(),
+            downloader: Arc::new(downloader),
+        }
+    }
+
+    /// Installs builds for the given platform instead of the detected one.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Installs into the given data directory instead of [`YVM_DATA_DIR`].
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the versions available for the platform, sorted in ascending order.
+    pub fn all_versions(&self) -> Result<Vec<Version>, YlemVmError> {
+        Ok(releases::all_releases(self.platform)?
+            .clone()
+            .into_versions())
+    }
+
+    /// Installs the provided version and returns the path to the ylem file.
+    pub fn install(&self, version: &Version) -> Result<PathBuf, YlemVmError> {
+        blocking_install_with_downloader(
+            &*self.downloader,
+            &self.config,
+            self.platform,
+            version,
+            &self.root,
+        )
+    }
+
+    /// Installs the provided version from the given list of releases and returns the path to
+    /// the ylem file.
+    pub fn install_release(
+        &self,
+        releases: &Releases,
+        version: &Version,
+    ) -> Result<PathBuf, YlemVmError> {
+        blocking_install_release_with_downloader(
+            &*self.downloader,
+            &self.config,
+            releases,
+            self.platform,
+            version,
+            &self.root,
+        )
+    }
+
+    /// Fetches a list of releases, see [`crate::fetch_releases`].
+    pub fn fetch_releases(&self, url: Url) -> Result<Releases, YlemVmError> {
+        releases::blocking_fetch_releases_with_downloader(&*self.downloader, url, &self.config)
+    }
+}
+
+impl fmt::Debug for Yvm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Yvm")
+            .field("config", &self.config)
+            .field("platform", &self.platform)
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,422 @@
+use semver::VersionReq;
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use url::Url;
+
+use crate::{
+    auth::{self, Secret, REDACTED},
+    error::{IoContext, YlemVmError},
+    lock::LOCK_TIMEOUT,
+    platform::EmulationFallback,
+    releases, NIXOS_PATCH_REQ, REQUEST_TIMEOUT, YVM_DATA_DIR,
+};
+
+/// Name of the per-project configuration file, looked up from the current directory upwards.
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".yvm.toml";
+
+/// Prefix of the environment variables overriding configuration keys, e.g. `YVM_AUTO_INSTALL`
+/// overrides `auto-install`.
+const ENV_PREFIX: &str = "YVM_";
+
+/// Configuration keys, in the order they are listed.
+pub const CONFIG_KEYS: [&str; 8] = [
+    "request-timeout",
+    "lock-timeout",
+    "releases-url",
+    "auth-token",
+    "nixos-patch",
+    "auto-install",
+    "cache-dir",
+    "emulation-fallback",
+];
+
+/// Keys that can't be set in a project configuration file, as cloning a repository must not
+/// make yvm send credentials to, or run binaries from, a host chosen by its authors.
+pub const GLOBAL_ONLY_KEYS: [&str; 3] = ["releases-url", "auth-token", "auto-install"];
+
+/// Derive path to the global configuration file.
+pub fn config_path() -> PathBuf {
+    YVM_DATA_DIR.join("config.toml")
+}
+
+/// Settings of yvm. Every field can be set in the global `config.toml` of the data directory, in
+/// a `.yvm.toml` file of the project and through a `YVM_*` environment variable, in increasing
+/// order of precedence. The [`GLOBAL_ONLY_KEYS`] can't be set per project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Timeout of requests downloading releases.
+    pub request_timeout: Duration,
+    /// How long to wait for another process installing the same version.
+    pub lock_timeout: Duration,
+    /// Base URL release artifacts are downloaded from, as `<releases-url>/<version>/<artifact>`.
+    pub releases_url: Url,
+    /// Bearer token sent to the host of the releases, see [`Auth::from_config`](crate::Auth).
+    pub auth_token: Option<Secret>,
+    /// Versions whose binaries are not fully static, hence need to be patched for NixOS.
+    pub nixos_patch: VersionReq,
+    /// Whether the `ylem` shim installs the requested version if it is missing.
+    pub auto_install: bool,
+    /// Directory downloaded artifacts are kept in, to install them again without downloading.
+    /// Nothing is cached if unset.
+    pub cache_dir: Option<PathBuf>,
+    /// Whether builds for an emulated architecture may be installed.
+    pub emulation_fallback: EmulationFallback,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            request_timeout: REQUEST_TIMEOUT,
+            lock_timeout: LOCK_TIMEOUT,
+            releases_url: Url::parse(releases::YLEM_RELEASES_URL).unwrap(),
+            auth_token: None,
+            nixos_patch: NIXOS_PATCH_REQ.clone(),
+            auto_install: false,
+            cache_dir: None,
+            emulation_fallback: EmulationFallback::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from the global configuration file, the project configuration
+    /// file of the current directory and the environment.
+    pub fn load() -> Result<Self, YlemVmError> {
+        let mut files = vec![config_path()];
+        if let Some(project) = env::current_dir()
+            .ok()
+            .and_then(|d| project_config_path(&d))
+        {
+            files.push(project);
+        }
+        Self::load_layers(&files, |name| env::var(name).ok())
+    }
+
+    /// Loads the configuration from the global configuration file only, so that it doesn't depend
+    /// on the current directory or the environment of the process. Used by the library functions
+    /// that don't take a configuration.
+    pub fn load_global() -> Result<Self, YlemVmError> {
+        Self::load_layers(&[config_path()], |_| None)
+    }
+
+    /// Applies the given configuration files, later ones taking precedence, and then the
+    /// environment variables returned by `env` on top of the defaults. Missing files are skipped.
+    fn load_layers(
+        files: &[PathBuf],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, YlemVmError> {
+        let mut config = Self::default();
+        for file in files {
+            for (key, value) in read_table(file)? {
+                check_file_key(file, &key)?;
+                let value = match value {
+                    toml::Value::String(s) => s,
+                    value => value.to_string(),
+                };
+                config.set(&key, &value)?;
+            }
+        }
+        for key in CONFIG_KEYS {
+            if let Some(value) = env(&env_var_name(key)) {
+                config.set(key, &value)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns the value of a configuration key, formatted the way [`Config::set`] accepts it.
+    /// Unset optional values are returned as an empty string, and a set `auth-token` as `***`.
+    pub fn get(&self, key: &str) -> Result<String, YlemVmError> {
+        Ok(match key {
+            "request-timeout" => self.request_timeout.as_secs().to_string(),
+            "lock-timeout" => self.lock_timeout.as_secs().to_string(),
+            "releases-url" => self.releases_url.to_string(),
+            "auth-token" => self
+                .auth_token
+                .as_ref()
+                .map(|_| REDACTED.to_string())
+                .unwrap_or_default(),
+            "nixos-patch" => self.nixos_patch.to_string(),
+            "auto-install" => self.auto_install.to_string(),
+            "cache-dir" => self
+                .cache_dir
+                .as_ref()
+                .map(|d| d.display().to_string())
+                .unwrap_or_default(),
+            "emulation-fallback" => self.emulation_fallback.to_string(),
+            key => return Err(YlemVmError::UnknownConfigKey(key.to_string())),
+        })
+    }
+
+    /// Validates and sets the value of a configuration key. Timeouts are given in seconds and an
+    /// empty `auth-token` or `cache-dir` unsets it.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), YlemVmError> {
+        let value = value.trim();
+        match key {
+            "request-timeout" => self.request_timeout = parse_secs(key, value)?,
+            "lock-timeout" => self.lock_timeout = parse_secs(key, value)?,
+            "releases-url" => {
+                let url = parse(key, value, Url::parse)?;
+                if url.cannot_be_a_base() {
+                    return Err(invalid(key, value, "not a base URL"));
+                }
+                self.releases_url = url;
+            }
+            "auth-token" if !auth::is_valid_credential(value) => {
+                return Err(invalid(key, REDACTED, "contains a control character"))
+            }
+            "auth-token" => self.auth_token = (!value.is_empty()).then(|| Secret::new(value)),
+            "nixos-patch" => self.nixos_patch = parse(key, value, VersionReq::parse)?,
+            "auto-install" => self.auto_install = parse(key, value, bool::from_str)?,
+            "cache-dir" => self.cache_dir = (!value.is_empty()).then(|| PathBuf::from(value)),
+            "emulation-fallback" => {
+                self.emulation_fallback = parse(key, value, EmulationFallback::from_str)?
+            }
+            key => return Err(YlemVmError::UnknownConfigKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Returns all keys and their values.
+    pub fn list(&self) -> Vec<(&'static str, String)> {
+        CONFIG_KEYS
+            .iter()
+            .map(|key| (*key, self.get(key).expect("known config key")))
+            .collect()
+    }
+
+    /// Sets a key in the given configuration file, keeping the other keys it contains. The value
+    /// is validated before the file is written.
+    pub fn set_in_file(path: &Path, key: &str, value: &str) -> Result<(), YlemVmError> {
+        let mut config = Self::default();
+        config.set(key, value)?;
+        check_file_key(path, key)?;
+
+        let mut table = read_table(path)?;
+        let value = match key {
+            "request-timeout" | "lock-timeout" => {
+                toml::Value::Integer(config.get(key)?.parse().expect("seconds fit in i64"))
+            }
+            "auto-install" => toml::Value::Boolean(config.auto_install),
+            "auth-token" => toml::Value::String(value.trim().to_string()),
+            _ => toml::Value::String(config.get(key)?),
+        };
+        table.insert(key.to_string(), value);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).io_context("create directory", parent)?;
+        }
+        fs::write(path, table.to_string()).io_context("write", path)?;
+        Ok(())
+    }
+}
+
+/// Finds the closest project configuration file in `dir` or its ancestors.
+pub fn project_config_path(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|p| p.is_file())
+}
+
+/// Fails if `key` is one of the [`GLOBAL_ONLY_KEYS`] and `path` a project configuration file.
+fn check_file_key(path: &Path, key: &str) -> Result<(), YlemVmError> {
+    let is_project = path.file_name() == Some(PROJECT_CONFIG_FILE_NAME.as_ref());
+    if is_project && GLOBAL_ONLY_KEYS.contains(&key) {
+        return Err(YlemVmError::InvalidConfigFile(
+            path.to_path_buf(),
+            format!(
+                "{key} can only be set in the global config or with {}",
+                env_var_name(key)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Name of the environment variable overriding a configuration key.
+pub fn env_var_name(key: &str) -> String {
+    format!("{ENV_PREFIX}{}", key.to_uppercase().replace('-', "_"))
+}
+
+fn read_table(path: &Path) -> Result<toml::Table, YlemVmError> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+    fs::read_to_string(path)
+        .io_context("read", path)?
+        .parse()
+        .map_err(|err: toml::de::Error| {
+            YlemVmError::InvalidConfigFile(path.to_path_buf(), err.message().to_string())
+        })
+}
+
+fn parse<T, E: fmt::Display>(
+    key: &str,
+    value: &str,
+    f: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<T, YlemVmError> {
+    f(value).map_err(|err| invalid(key, value, &err.to_string()))
+}
+
+fn parse_secs(key: &str, value: &str) -> Result<Duration, YlemVmError> {
+    match parse(key, value, u64::from_str)? {
+        0 => Err(invalid(key, value, "must be at least one second")),
+        secs => Ok(Duration::from_secs(secs)),
+    }
+}
+
+fn invalid(key: &str, value: &str, reason: &str) -> YlemVmError {
+    YlemVmError::InvalidConfigValue {
+        key: key.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_set_roundtrip() {
+        let mut config = Config::default();
+        for (key, value) in [
+            ("request-timeout", "30"),
+            ("lock-timeout", "5"),
+            ("releases-url", "http://localhost:8080/releases"),
+            ("nixos-patch", ">=1.0.0"),
+            ("auto-install", "true"),
+            ("cache-dir", "/tmp/yvm-cache"),
+            ("emulation-fallback", "never"),
+        ] {
+            config.set(key, value).unwrap();
+            assert_eq!(config.get(key).unwrap(), value);
+        }
+        config.set("cache-dir", "").unwrap();
+        assert_eq!(config.cache_dir, None);
+        config.set("auth-token", "token").unwrap();
+        assert_eq!(config.auth_token, Some(Secret::new("token")));
+        config.set("auth-token", "").unwrap();
+        assert_eq!(config.auth_token, None);
+        assert_eq!(config.list().len(), CONFIG_KEYS.len());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let mut config = Config::default();
+        assert!(matches!(
+            config.set("colour", "red"),
+            Err(YlemVmError::UnknownConfigKey(key)) if key == "colour"
+        ));
+        for (key, value) in [
+            ("request-timeout", "soon"),
+            ("lock-timeout", "0"),
+            ("releases-url", "not a url"),
+            ("releases-url", "mailto:someone@example.com"),
+            ("nixos-patch", "~>"),
+            ("auto-install", "yes"),
+            ("emulation-fallback", "sometimes"),
+        ] {
+            assert!(matches!(
+                config.set(key, value),
+                Err(YlemVmError::InvalidConfigValue { key: k, .. }) if k == key
+            ));
+        }
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn layers_files_and_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let global = dir.path().join("config.toml");
+        let project = dir.path().join(PROJECT_CONFIG_FILE_NAME);
+        Config::set_in_file(&global, "request-timeout", "10").unwrap();
+        Config::set_in_file(&global, "auto-install", "true").unwrap();
+        Config::set_in_file(&global, "auth-token", "token").unwrap();
+        Config::set_in_file(&project, "request-timeout", "20").unwrap();
+        assert_eq!(
+            fs::read_to_string(&global).unwrap(),
+            "auth-token = \"token\"\nauto-install = true\nrequest-timeout = 10\n"
+        );
+
+        let config = Config::load_layers(&[global.clone(), project.clone()], |name| {
+            (name == "YVM_AUTO_INSTALL").then(|| "false".to_string())
+        })
+        .unwrap();
+        assert_eq!(config.request_timeout, Duration::from_secs(20));
+        assert!(!config.auto_install);
+        assert_eq!(config.auth_token, Some(Secret::new("token")));
+
+        let missing = Config::load_layers(&[dir.path().join("missing.toml")], |_| None).unwrap();
+        assert_eq!(missing, Config::default());
+    }
+
+    #[test]
+    fn keeps_global_only_keys_out_of_projects() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(PROJECT_CONFIG_FILE_NAME);
+        for (key, value) in [
+            ("releases-url", "https://attacker.example.com"),
+            ("auth-token", "token"),
+            ("auto-install", "true"),
+        ] {
+            assert!(matches!(
+                Config::set_in_file(&project, key, value),
+                Err(YlemVmError::InvalidConfigFile(..))
+            ));
+        }
+        assert!(!project.exists());
+
+        // a cloned repository that redirects downloads
+        fs::write(&project, "releases-url = \"https://attacker.example.com\"").unwrap();
+        assert!(matches!(
+            Config::load_layers(&[project], |_| None),
+            Err(YlemVmError::InvalidConfigFile(path, reason))
+                if path.ends_with(PROJECT_CONFIG_FILE_NAME) && reason.contains("YVM_RELEASES_URL")
+        ));
+    }
+
+    #[test]
+    fn reports_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.toml");
+        fs::write(&file, "request-timeout = ").unwrap();
+        assert!(matches!(
+            Config::load_layers(std::slice::from_ref(&file), |_| None),
+            Err(YlemVmError::InvalidConfigFile(path, _)) if path == file
+        ));
+        fs::write(&file, "auto-install = \"maybe\"").unwrap();
+        assert!(matches!(
+            Config::load_layers(&[file], |_| None),
+            Err(YlemVmError::InvalidConfigValue { .. })
+        ));
+        assert!(matches!(
+            Config::set_in_file(&dir.path().join("new.toml"), "lock-timeout", "-1"),
+            Err(YlemVmError::InvalidConfigValue { .. })
+        ));
+        assert!(!dir.path().join("new.toml").exists());
+    }
+
+    #[test]
+    fn finds_project_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(project_config_path(&nested), None);
+        fs::write(dir.path().join(PROJECT_CONFIG_FILE_NAME), "").unwrap();
+        assert_eq!(
+            project_config_path(&nested),
+            Some(dir.path().join(PROJECT_CONFIG_FILE_NAME))
+        );
+    }
+
+    #[test]
+    fn env_var_names() {
+        assert_eq!(env_var_name("auto-install"), "YVM_AUTO_INSTALL");
+        assert_eq!(env_var_name("emulation-fallback"), "YVM_EMULATION_FALLBACK");
+    }
+}
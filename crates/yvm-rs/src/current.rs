@@ -0,0 +1,188 @@
+//! The `current` link to the directory of the global version, for consumers that can't run the
+//! `ylem` shim, such as IDE integrations and Docker layers.
+
+use semver::Version;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(not(target_family = "unix"))]
+use crate::version_dir;
+use crate::{error::IoContext, YlemVmError, YVM_DATA_DIR};
+
+const CURRENT: &str = "current";
+
+/// Derive path to the link to the directory of the global version. The binary of the global
+/// version is at `current/ylem-<version>`.
+pub fn current_path() -> PathBuf {
+    YVM_DATA_DIR.join(CURRENT)
+}
+
+/// Whether a data directory entry is the `current` link, or a temporary link replacing it.
+pub(crate) fn is_current_entry(name: &str) -> bool {
+    name == CURRENT || name.starts_with(".current.")
+}
+
+/// What the [`current_path`] link points to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CurrentLink {
+    /// There is no link, because the global version is not set.
+    Missing,
+    /// The link points to the directory of this installed version.
+    Version(Version),
+    /// The link points to a path that is not a version directory, e.g. of a version that was
+    /// removed by hand.
+    Dangling(PathBuf),
+}
+
+/// Reads where the [`current_path`] link points to.
+pub fn current_link() -> Result<CurrentLink, YlemVmError> {
+    read_current_link(&YVM_DATA_DIR)
+}
+
+pub(crate) fn read_current_link(root: &Path) -> Result<CurrentLink, YlemVmError> {
+    let current = root.join(CURRENT);
+    let target = match fs::read_link(&current) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(CurrentLink::Missing),
+        target => target.io_context("read link", &current)?,
+    };
+    let version = target
+        .file_name()
+        .and_then(|name| Version::parse(&name.to_string_lossy()).ok());
+    match version {
+        // relative targets are relative to the data directory
+        Some(version) if root.join(&target).is_dir() => Ok(CurrentLink::Version(version)),
+        _ => Ok(CurrentLink::Dangling(target)),
+    }
+}
+
+/// Points the `current` link of the data directory `root` to the directory of `version`, or
+/// removes it. On unix the new link replaces the old one atomically, so readers always see either.
+pub(crate) fn link_current(root: &Path, version: Option<&Version>) -> Result<(), YlemVmError> {
+    let current = root.join(CURRENT);
+    let Some(version) = version else {
+        return match remove_link(&current) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            res => res.io_context("remove link", &current),
+        };
+    };
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // concurrent updates, even from the same process, each use their own temporary link
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let tmp = root.join(format!(
+            ".current.{}.{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::os::unix::fs::symlink(version.to_string(), &tmp).io_context("create link", &tmp)?;
+        if let Err(err) = fs::rename(&tmp, &current) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err).io_context("replace link", &current);
+        }
+    }
+    // directory symlinks need extra privileges on Windows, junctions don't. A junction can't
+    // replace another one, so the old link is removed first.
+    #[cfg(not(target_family = "unix"))]
+    {
+        match remove_link(&current) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                return Err(err).io_context("remove link", &current)
+            }
+            _ => {}
+        }
+        let output = std::process::Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(&current)
+            .arg(version_dir(root, version))
+            .output()
+            .io_context("run mklink to create", &current)?;
+        if !output.status.success() {
+            let message = format!(
+                "mklink failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return Err(io::Error::new(io::ErrorKind::Other, message))
+                .io_context("create link", &current);
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_link(link: &Path) -> io::Result<()> {
+    // links to directories are directories themselves on Windows
+    #[cfg(not(target_family = "unix"))]
+    if link.is_dir() {
+        return fs::remove_dir(link);
+    }
+    fs::remove_file(link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version_dir;
+
+    #[test]
+    fn links_global_version() {
+        let root = tempfile::tempdir().unwrap();
+        let (v1, v2) = (Version::new(1, 1, 1), Version::new(1, 1, 2));
+        for version in [&v1, &v2] {
+            fs::create_dir_all(version_dir(root.path(), version)).unwrap();
+        }
+        assert_eq!(
+            read_current_link(root.path()).unwrap(),
+            CurrentLink::Missing
+        );
+
+        link_current(root.path(), Some(&v1)).unwrap();
+        assert_eq!(
+            read_current_link(root.path()).unwrap(),
+            CurrentLink::Version(v1)
+        );
+        link_current(root.path(), Some(&v2)).unwrap();
+        assert_eq!(
+            read_current_link(root.path()).unwrap(),
+            CurrentLink::Version(v2)
+        );
+        // no temporary links are left behind
+        let entries = fs::read_dir(root.path()).unwrap().count();
+        assert_eq!(entries, 3);
+
+        link_current(root.path(), None).unwrap();
+        assert_eq!(
+            read_current_link(root.path()).unwrap(),
+            CurrentLink::Missing
+        );
+        link_current(root.path(), None).unwrap();
+    }
+
+    #[test]
+    fn detects_dangling_link() {
+        let root = tempfile::tempdir().unwrap();
+        let version = Version::new(1, 1, 2);
+        fs::create_dir_all(version_dir(root.path(), &version)).unwrap();
+        link_current(root.path(), Some(&version)).unwrap();
+
+        fs::remove_dir_all(version_dir(root.path(), &version)).unwrap();
+        assert!(matches!(
+            read_current_link(root.path()).unwrap(),
+            CurrentLink::Dangling(target) if target.ends_with("1.1.2")
+        ));
+    }
+
+    #[test]
+    fn recognizes_current_entries() {
+        assert!(is_current_entry("current"));
+        assert!(is_current_entry(".current.42.0"));
+        assert!(!is_current_entry("1.1.2"));
+    }
+}
@@ -0,0 +1,577 @@
+use semver::Version;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    bin_path, current_link, current_path,
+    error::IoContext,
+    global_version_path, is_reserved_entry,
+    lock::{self, LockFile},
+    lock_files, platform, unset_global_version, use_version, CurrentLink, YlemVmError,
+    YVM_DATA_DIR,
+};
+
+/// How serious a [`Finding`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// yvm works, but possibly not as the user expects.
+    Warning,
+    /// Some yvm commands or the `ylem` shim will fail.
+    Error,
+}
+
+/// An automatic remedy for a [`Finding`], applied with [`apply_fix`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fix {
+    /// Remove a file, or the directory of a broken version, from the data directory.
+    Remove(PathBuf),
+    /// Set the global version.
+    UseVersion(Version),
+    /// Clear the global version.
+    UnsetGlobalVersion,
+    /// Add the executable bits to a ylem binary.
+    MakeExecutable(PathBuf),
+    /// Remove an install lock file, unless it got locked since.
+    RemoveLock(PathBuf),
+}
+
+/// A problem found by [`diagnose`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// What is wrong.
+    pub message: String,
+    /// What the user can do about it by hand.
+    pub hint: Option<String>,
+    /// How yvm can fix it, if it can.
+    pub fix: Option<Fix>,
+}
+
+impl Finding {
+    fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            hint: None,
+            fix: None,
+        }
+    }
+
+    fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    fn fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Checks the platform, the data directory, the global version and its `current` link, the
+/// `ylem` shim on `PATH`, the NixOS patching requirements and the install locks. Returns the problems found.
+pub fn diagnose() -> Result<Vec<Finding>, YlemVmError> {
+    let mut findings = vec![];
+    let path_var = env::var_os("PATH");
+
+    check_platform(platform::platform(), &mut findings);
+    let installed = check_data_dir(YVM_DATA_DIR.as_path(), &mut findings)?;
+    let global_version = fs::read_to_string(global_version_path()).unwrap_or_default();
+    check_global_version(&global_version, &installed, &mut findings);
+    // a broken global version is fixed by setting another one, which relinks `current` too
+    let global = Version::parse(global_version.trim()).ok();
+    if global_version.trim().is_empty() || global.as_ref().map_or(false, |v| installed.contains(v))
+    {
+        check_current_link(
+            current_link(),
+            global.as_ref(),
+            &current_path(),
+            &mut findings,
+        );
+    }
+    check_shim(path_var.clone(), &bin_path(), &mut findings);
+    check_nixos(platform::is_nixos(), path_var, &mut findings);
+
+    for lock in lock_files()?.into_iter().filter(|lock| lock.stale) {
+        findings.push(
+            Finding::new(
+                Severity::Warning,
+                format!("Stale install lock {}", lock.path.display()),
+            )
+            .fix(Fix::RemoveLock(lock.path)),
+        );
+    }
+
+    Ok(findings)
+}
+
+/// Applies the given fix.
+pub fn apply_fix(fix: &Fix) -> Result<(), YlemVmError> {
+    match fix {
+        Fix::Remove(path) if path.is_dir() => {
+            // an install may have started since, creating the directory before the binary
+            let _lock = match path.file_name().map(|name| name.to_string_lossy()) {
+                Some(name) => try_lock_version(path.parent().unwrap_or(path), &name)?,
+                None => None,
+            };
+            fs::remove_dir_all(path).io_context("remove directory", path)?
+        }
+        Fix::Remove(path) => fs::remove_file(path).io_context("remove", path)?,
+        Fix::RemoveLock(path) => {
+            lock::remove_lock_file(path)?;
+        }
+        Fix::UseVersion(version) => use_version(version)?,
+        Fix::UnsetGlobalVersion => unset_global_version()?,
+        #[cfg(target_family = "unix")]
+        Fix::MakeExecutable(path) => {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = fs::metadata(path)
+                .io_context("read metadata of", path)?
+                .permissions();
+            perms.set_mode(perms.mode() | 0o111);
+            fs::set_permissions(path, perms).io_context("set permissions of", path)?;
+        }
+        #[cfg(not(target_family = "unix"))]
+        Fix::MakeExecutable(_) => {}
+    }
+    Ok(())
+}
+
+fn check_platform(platform: platform::Platform, findings: &mut Vec<Finding>) {
+    if platform == platform::Platform::Unsupported {
+        findings.push(
+            Finding::new(
+                Severity::Error,
+                format!(
+                    "Unsupported platform {}-{}, no ylem builds are available",
+                    env::consts::OS,
+                    env::consts::ARCH
+                ),
+            )
+            .hint("Build ylem from source, yvm can't install it on this platform"),
+        );
+    }
+}
+
+/// Checks every entry of the data directory and returns the versions that are fully installed.
+fn check_data_dir(dir: &Path, findings: &mut Vec<Finding>) -> Result<Vec<Version>, YlemVmError> {
+    let mut installed = vec![];
+    if !dir.exists() {
+        return Ok(installed);
+    }
+
+    for entry in fs::read_dir(dir).io_context("read directory", dir)? {
+        let entry = entry.io_context("read directory", dir)?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if is_reserved_entry(&name) {
+            continue;
+        }
+
+        let version = match Version::parse(&name) {
+            Ok(version) if entry.path().is_dir() => version,
+            // might be the user's, so it's left to them
+            _ => {
+                findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        format!(
+                            "Unexpected entry {} breaks listing installed versions",
+                            entry.path().display()
+                        ),
+                    )
+                    .hint("Move it out of the data directory"),
+                );
+                continue;
+            }
+        };
+
+        let binary = entry.path().join(format!("ylem-{version}"));
+        if !binary.is_file() {
+            // the directory of a running install has no binary yet
+            if let Err(YlemVmError::Timeout(..)) = try_lock_version(dir, &name) {
+                continue;
+            }
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    format!("Ylem {version} is missing its binary {}", binary.display()),
+                )
+                .hint(format!("Reinstall it with `yvm install {version}`"))
+                .fix(Fix::Remove(entry.path())),
+            );
+            continue;
+        }
+
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(&binary).io_context("read metadata of", &binary)?;
+            if metadata.permissions().mode() & 0o111 == 0 {
+                findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        format!(
+                            "Ylem {version} binary {} is not executable",
+                            binary.display()
+                        ),
+                    )
+                    .fix(Fix::MakeExecutable(binary)),
+                );
+            }
+        }
+
+        installed.push(version);
+    }
+
+    installed.sort();
+    Ok(installed)
+}
+
+/// Takes the install lock of the version directory `name` in the data directory `root` without
+/// waiting, failing with [`YlemVmError::Timeout`] while it is being installed. Returns None if
+/// `name` is not a version.
+fn try_lock_version(root: &Path, name: &str) -> Result<Option<LockFile>, YlemVmError> {
+    match Version::parse(name) {
+        Ok(version) => lock::try_lock_file(root, &version, Duration::ZERO).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+fn check_global_version(content: &str, installed: &[Version], findings: &mut Vec<Finding>) {
+    let fallback = match installed.last() {
+        Some(latest) => Fix::UseVersion(latest.clone()),
+        None => Fix::UnsetGlobalVersion,
+    };
+
+    let content = content.trim();
+    if content.is_empty() {
+        if let Some(latest) = installed.last() {
+            findings.push(
+                Finding::new(Severity::Warning, "Global version is not set")
+                    .hint("Pick one with `yvm use <version>`")
+                    .fix(Fix::UseVersion(latest.clone())),
+            );
+        }
+        return;
+    }
+
+    match Version::parse(content) {
+        Err(_) => findings.push(
+            Finding::new(
+                Severity::Error,
+                format!("Global version file contains an invalid version {content:?}"),
+            )
+            .fix(fallback),
+        ),
+        Ok(version) if !installed.contains(&version) => findings.push(
+            Finding::new(
+                Severity::Error,
+                format!("Global version {version} is not installed"),
+            )
+            .hint(format!("Install it with `yvm install {version}`"))
+            .fix(fallback),
+        ),
+        Ok(_) => {}
+    }
+}
+
+/// Checks that the `current` link points to the installed global version, or is missing if the
+/// global version is not set.
+fn check_current_link(
+    link: Result<CurrentLink, YlemVmError>,
+    global: Option<&Version>,
+    current: &Path,
+    findings: &mut Vec<Finding>,
+) {
+    let relink = || match global {
+        Some(version) => Fix::UseVersion(version.clone()),
+        None => Fix::Remove(current.to_path_buf()),
+    };
+    match (link, global) {
+        (Err(_), _) => findings.push(
+            Finding::new(
+                Severity::Error,
+                format!("{} is not a link to a version directory", current.display()),
+            )
+            .fix(Fix::Remove(current.to_path_buf())),
+        ),
+        (Ok(CurrentLink::Dangling(target)), _) => findings.push(
+            Finding::new(
+                Severity::Error,
+                format!(
+                    "{} points to {}, which is not an installed version",
+                    current.display(),
+                    target.display()
+                ),
+            )
+            .fix(relink()),
+        ),
+        (Ok(CurrentLink::Version(linked)), Some(global)) if &linked != global => findings.push(
+            Finding::new(
+                Severity::Warning,
+                format!(
+                    "{} points to Ylem {linked} instead of the global version {global}",
+                    current.display()
+                ),
+            )
+            .fix(relink()),
+        ),
+        (Ok(CurrentLink::Version(linked)), None) => findings.push(
+            Finding::new(
+                Severity::Warning,
+                format!(
+                    "{} points to Ylem {linked}, but the global version is not set",
+                    current.display()
+                ),
+            )
+            .fix(relink()),
+        ),
+        (Ok(CurrentLink::Missing), Some(global)) => findings.push(
+            Finding::new(
+                Severity::Warning,
+                format!(
+                    "{} to the global version {global} is missing",
+                    current.display()
+                ),
+            )
+            .fix(relink()),
+        ),
+        _ => {}
+    }
+}
+
+fn check_shim(path_var: Option<OsString>, bin_dir: &Path, findings: &mut Vec<Finding>) {
+    let ylems = find_in_path("ylem", path_var.clone());
+    let Some(first) = ylems.first() else {
+        findings.push(
+            Finding::new(Severity::Warning, "No ylem executable found on PATH")
+                .hint("Add `eval \"$(yvm env)\"` to your shell's rc file"),
+        );
+        return;
+    };
+
+    let shim = bin_dir.join(format!("ylem{}", env::consts::EXE_SUFFIX));
+    if shim.exists() && !same_file(first, &shim) {
+        let message = if ylems.iter().any(|ylem| same_file(ylem, &shim)) {
+            format!("{} comes before the yvm shim on PATH", first.display())
+        } else {
+            format!(
+                "The yvm shim {} is not on PATH, {} is used instead",
+                shim.display(),
+                first.display()
+            )
+        };
+        findings.push(
+            Finding::new(Severity::Warning, message)
+                .hint(format!("Put {} first on PATH", bin_dir.display())),
+        );
+    }
+}
+
+/// Returns the `ylem` executables that come before the yvm shim in `bin_dir` on `PATH`, and are
+/// run instead of it. Returns none if the shim is not on `PATH` at all.
+pub fn ylems_before_shim(path_var: Option<OsString>, bin_dir: &Path) -> Vec<PathBuf> {
+    let shim = bin_dir.join(format!("ylem{}", env::consts::EXE_SUFFIX));
+    let mut ylems = find_in_path("ylem", path_var);
+    match ylems.iter().position(|ylem| same_file(ylem, &shim)) {
+        Some(i) => {
+            ylems.truncate(i);
+            ylems
+        }
+        None => vec![],
+    }
+}
+
+fn check_nixos(is_nixos: bool, path_var: Option<OsString>, findings: &mut Vec<Finding>) {
+    if is_nixos && find_in_path("nix-shell", path_var).is_empty() {
+        findings.push(
+            Finding::new(
+                Severity::Error,
+                "NixOS detected, but nix-shell is not available to patch ylem binaries with patchelf",
+            )
+            .hint("Make sure nix-shell is on PATH before installing versions"),
+        );
+    }
+}
+
+fn find_in_path(name: &str, path_var: Option<OsString>) -> Vec<PathBuf> {
+    let file_name = format!("{name}{}", env::consts::EXE_SUFFIX);
+    path_var
+        .map(|paths| {
+            env::split_paths(&paths)
+                .map(|dir| dir.join(&file_name))
+                .filter(|path| path.is_file())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_broken_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".global-version"), "").unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        fs::create_dir_all(dir.path().join("1.0.0")).unwrap();
+        fs::create_dir_all(dir.path().join("1.1.2")).unwrap();
+        fs::write(dir.path().join("1.1.2").join("ylem-1.1.2"), "").unwrap();
+
+        let mut findings = vec![];
+        let installed = check_data_dir(dir.path(), &mut findings).unwrap();
+        assert_eq!(installed, vec![Version::new(1, 1, 2)]);
+
+        let fixes = findings
+            .iter()
+            .filter_map(|f| f.fix.clone())
+            .collect::<Vec<_>>();
+        assert!(fixes.contains(&Fix::Remove(dir.path().join("1.0.0"))));
+        assert!(!fixes.contains(&Fix::Remove(dir.path().join("notes.txt"))));
+        assert!(findings.iter().any(|f| f.message.contains("notes.txt")));
+    }
+
+    #[test]
+    fn leaves_running_installs_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let version = Version::new(1, 1, 2);
+        fs::create_dir_all(dir.path().join("1.1.2")).unwrap();
+        let lock = lock::try_lock_file(dir.path(), &version, lock::LOCK_TIMEOUT).unwrap();
+
+        let mut findings = vec![];
+        check_data_dir(dir.path(), &mut findings).unwrap();
+        assert!(findings.is_empty());
+        let fix = Fix::Remove(dir.path().join("1.1.2"));
+        assert!(matches!(apply_fix(&fix), Err(YlemVmError::Timeout(..))));
+        assert!(dir.path().join("1.1.2").exists());
+
+        drop(lock);
+        check_data_dir(dir.path(), &mut findings).unwrap();
+        assert_eq!(findings.pop().unwrap().fix, Some(fix.clone()));
+        apply_fix(&fix).unwrap();
+        assert!(!dir.path().join("1.1.2").exists());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn fixes_missing_exec_bit() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("1.1.2").join("ylem-1.1.2");
+        fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        fs::write(&binary, "").unwrap();
+
+        let mut findings = vec![];
+        check_data_dir(dir.path(), &mut findings).unwrap();
+        assert_eq!(findings[0].fix, Some(Fix::MakeExecutable(binary.clone())));
+
+        apply_fix(&Fix::MakeExecutable(binary)).unwrap();
+        findings.clear();
+        check_data_dir(dir.path(), &mut findings).unwrap();
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn checks_global_version() {
+        let installed = vec![Version::new(1, 0, 0), Version::new(1, 1, 2)];
+        let latest = Some(Fix::UseVersion(Version::new(1, 1, 2)));
+
+        let mut findings = vec![];
+        check_global_version("1.0.0\n", &installed, &mut findings);
+        assert!(findings.is_empty());
+
+        check_global_version("", &installed, &mut findings);
+        assert_eq!(findings.pop().unwrap().fix, latest);
+
+        check_global_version("1.0.1", &installed, &mut findings);
+        assert_eq!(findings.pop().unwrap().severity, Severity::Error);
+
+        check_global_version("latest", &[], &mut findings);
+        assert_eq!(findings.pop().unwrap().fix, Some(Fix::UnsetGlobalVersion));
+
+        check_global_version("", &[], &mut findings);
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn checks_current_link() {
+        let current = Path::new("current");
+        let (v1, v2) = (Version::new(1, 1, 1), Version::new(1, 1, 2));
+        let check = |link, global| {
+            let mut findings = vec![];
+            check_current_link(Ok(link), global, current, &mut findings);
+            findings.pop().and_then(|finding| finding.fix)
+        };
+
+        assert_eq!(check(CurrentLink::Version(v2.clone()), Some(&v2)), None);
+        assert_eq!(check(CurrentLink::Missing, None), None);
+        assert_eq!(
+            check(CurrentLink::Version(v1), Some(&v2)),
+            Some(Fix::UseVersion(v2.clone()))
+        );
+        assert_eq!(
+            check(CurrentLink::Missing, Some(&v2)),
+            Some(Fix::UseVersion(v2.clone()))
+        );
+        assert_eq!(
+            check(CurrentLink::Dangling("1.0.0".into()), None),
+            Some(Fix::Remove(current.to_path_buf()))
+        );
+        assert_eq!(
+            check(CurrentLink::Version(v2.clone()), None),
+            Some(Fix::Remove(current.to_path_buf()))
+        );
+    }
+
+    #[test]
+    fn checks_shim_on_path() {
+        let bin_dir = tempfile::tempdir().unwrap();
+        let other_dir = tempfile::tempdir().unwrap();
+        let name = format!("ylem{}", env::consts::EXE_SUFFIX);
+        fs::write(bin_dir.path().join(&name), "").unwrap();
+        fs::write(other_dir.path().join(&name), "").unwrap();
+
+        let mut findings = vec![];
+        check_shim(None, bin_dir.path(), &mut findings);
+        assert_eq!(findings.pop().unwrap().severity, Severity::Warning);
+
+        let path = env::join_paths([bin_dir.path(), other_dir.path()]).unwrap();
+        check_shim(Some(path), bin_dir.path(), &mut findings);
+        assert!(findings.is_empty());
+
+        let path = env::join_paths([other_dir.path(), bin_dir.path()]).unwrap();
+        check_shim(Some(path.clone()), bin_dir.path(), &mut findings);
+        assert!(findings.pop().unwrap().message.contains("comes before"));
+
+        assert_eq!(
+            ylems_before_shim(Some(path), bin_dir.path()),
+            vec![other_dir.path().join(&name)]
+        );
+        let path = env::join_paths([bin_dir.path(), other_dir.path()]).unwrap();
+        assert!(ylems_before_shim(Some(path), bin_dir.path()).is_empty());
+        let path = env::join_paths([other_dir.path()]).unwrap();
+        assert!(ylems_before_shim(Some(path), bin_dir.path()).is_empty());
+    }
+
+    #[test]
+    fn checks_nixos_patching() {
+        let mut findings = vec![];
+        check_nixos(false, None, &mut findings);
+        assert!(findings.is_empty());
+
+        check_nixos(true, None, &mut findings);
+        assert_eq!(findings.pop().unwrap().severity, Severity::Error);
+    }
+}
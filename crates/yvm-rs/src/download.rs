@@ -0,0 +1,300 @@
+//! Transports the installer downloads release lists and artifacts with.
+//!
+//! The installer only talks to a [`Downloader`], or a [`BlockingDownloader`] in the blocking API,
+//! so it doesn't depend on an HTTP client or async runtime itself. The reqwest-backed defaults
+//! are enabled by the `rustls` and `openssl` features, and [`LocalDownloader`] serves files and
+//! in-memory bodies for tests and offline bundles.
+//!
+//! Credentials, see [`Auth`](crate::Auth), are only sent by the reqwest-backed downloaders.
+
+use http::StatusCode;
+use std::{collections::HashMap, fs, future::Future, pin::Pin, time::Duration};
+use url::Url;
+
+use crate::error::YlemVmError;
+#[cfg(feature = "reqwest")]
+use crate::{
+    auth::{Auth, GithubAsset},
+    Config,
+};
+#[cfg(feature = "reqwest")]
+use http::header::{ACCEPT, AUTHORIZATION};
+
+/// Future returned by [`Downloader::download`].
+pub type DownloadFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Vec<u8>, YlemVmError>> + Send + 'a>>;
+
+/// Downloads the bodies of URLs for the async installer.
+pub trait Downloader: Send + Sync {
+    /// Downloads the body of `url` within `timeout`, failing with
+    /// [`YlemVmError::UnsuccessfulResponse`] if the server doesn't have it.
+    fn download<'a>(&'a self, url: &'a Url, timeout: Duration) -> DownloadFuture<'a>;
+}
+
+/// Downloads the bodies of URLs for the blocking installer.
+pub trait BlockingDownloader: Send + Sync {
+    /// Downloads the body of `url` within `timeout`, failing with
+    /// [`YlemVmError::UnsuccessfulResponse`] if the server doesn't have it.
+    fn download(&self, url: &Url, timeout: Duration) -> Result<Vec<u8>, YlemVmError>;
+}
+
+/// User agent of the reqwest clients, which the GitHub API requires.
+#[cfg(feature = "reqwest")]
+const USER_AGENT: &str = concat!("yvm-rs/", env!("CARGO_PKG_VERSION"));
+
+/// `Accept` headers of the requests resolving private GitHub release assets.
+#[cfg(feature = "reqwest")]
+const GITHUB_RELEASE: &str = "application/vnd.github+json";
+#[cfg(feature = "reqwest")]
+const GITHUB_ASSET: &str = "application/octet-stream";
+
+/// Downloads with an async reqwest client, sending the credentials to the release host if any.
+///
+/// Assets of private GitHub releases are downloaded through the API, whose redirect to the file
+/// is followed without the credentials, as reqwest drops them on redirects to another host.
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Debug)]
+pub struct ReqwestDownloader {
+    client: reqwest::Client,
+    auth: Option<Auth>,
+}
+
+#[cfg(feature = "reqwest")]
+impl Default for ReqwestDownloader {
+    fn default() -> Self {
+        Self::new(
+            reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .expect("reqwest::Client::new()"),
+        )
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestDownloader {
+    /// Downloads with the given client, e.g. one configured with a proxy.
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client, auth: None }
+    }
+
+    /// Downloads with the credentials of the configured release host, see
+    /// [`Auth::from_config`].
+    pub fn for_config(config: &Config) -> Result<Self, YlemVmError> {
+        Ok(Self {
+            auth: Auth::from_config(config)?,
+            ..Default::default()
+        })
+    }
+
+    /// Sends the given credentials to their host.
+    pub fn auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    async fn get(
+        &self,
+        url: &Url,
+        accept: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, YlemVmError> {
+        let mut req = self.client.get(url.clone()).timeout(timeout);
+        if let Some(auth) = self.auth.as_ref().filter(|auth| auth.applies_to(url)) {
+            req = req.header(AUTHORIZATION, auth.header()?);
+        }
+        if let Some(accept) = accept {
+            req = req.header(ACCEPT, accept);
+        }
+        let res = req.send().await?;
+
+        if !res.status().is_success() {
+            return Err(YlemVmError::UnsuccessfulResponse(url.clone(), res.status()));
+        }
+        Ok(res.bytes().await?.to_vec())
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Downloader for ReqwestDownloader {
+    fn download<'a>(&'a self, url: &'a Url, timeout: Duration) -> DownloadFuture<'a> {
+        Box::pin(async move {
+            match private_github_asset(self.auth.as_ref(), url) {
+                Some(asset) => {
+                    let release = asset.release_url();
+                    let release = self.get(&release, Some(GITHUB_RELEASE), timeout).await?;
+                    let asset = asset.asset_url(&release).ok_or_else(|| not_found(url))?;
+                    self.get(&asset, Some(GITHUB_ASSET), timeout).await
+                }
+                None => self.get(url, None, timeout).await,
+            }
+        })
+    }
+}
+
+/// Downloads with a blocking reqwest client, see [`ReqwestDownloader`].
+#[cfg(feature = "blocking")]
+#[derive(Clone, Debug)]
+pub struct BlockingReqwestDownloader {
+    client: reqwest::blocking::Client,
+    auth: Option<Auth>,
+}
+
+#[cfg(feature = "blocking")]
+impl Default for BlockingReqwestDownloader {
+    fn default() -> Self {
+        Self::new(
+            reqwest::blocking::Client::builder()
+                .user_agent(USER_AGENT)
+                .build()
+                .expect("reqwest::Client::new()"),
+        )
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingReqwestDownloader {
+    /// Downloads with the given client, e.g. one configured with a proxy.
+    pub fn new(client: reqwest::blocking::Client) -> Self {
+        Self { client, auth: None }
+    }
+
+    /// Downloads with the credentials of the configured release host, see
+    /// [`Auth::from_config`].
+    pub fn for_config(config: &Config) -> Result<Self, YlemVmError> {
+        Ok(Self {
+            auth: Auth::from_config(config)?,
+            ..Default::default()
+        })
+    }
+
+    /// Sends the given credentials to their host.
+    pub fn auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    fn get(
+        &self,
+        url: &Url,
+        accept: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, YlemVmError> {
+        let mut req = self.client.get(url.clone()).timeout(timeout);
+        if let Some(auth) = self.auth.as_ref().filter(|auth| auth.applies_to(url)) {
+            req = req.header(AUTHORIZATION, auth.header()?);
+        }
+        if let Some(accept) = accept {
+            req = req.header(ACCEPT, accept);
+        }
+        let res = req.send()?;
+
+        if !res.status().is_success() {
+            return Err(YlemVmError::UnsuccessfulResponse(url.clone(), res.status()));
+        }
+        Ok(res.bytes()?.to_vec())
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingDownloader for BlockingReqwestDownloader {
+    fn download(&self, url: &Url, timeout: Duration) -> Result<Vec<u8>, YlemVmError> {
+        match private_github_asset(self.auth.as_ref(), url) {
+            Some(asset) => {
+                let release = self.get(&asset.release_url(), Some(GITHUB_RELEASE), timeout)?;
+                let asset = asset.asset_url(&release).ok_or_else(|| not_found(url))?;
+                self.get(&asset, Some(GITHUB_ASSET), timeout)
+            }
+            None => self.get(url, None, timeout),
+        }
+    }
+}
+
+/// Returns the GitHub release asset at `url` if it has to be resolved through the API, which is
+/// the case when there are credentials for it.
+#[cfg(feature = "reqwest")]
+fn private_github_asset(auth: Option<&Auth>, url: &Url) -> Option<GithubAsset> {
+    auth.filter(|auth| auth.applies_to(url))?;
+    GithubAsset::parse(url)
+}
+
+fn not_found(url: &Url) -> YlemVmError {
+    YlemVmError::UnsuccessfulResponse(url.clone(), StatusCode::NOT_FOUND)
+}
+
+/// Serves `file://` URLs from the file system and other URLs from bodies registered with
+/// [`LocalDownloader::insert`], without any network access. Point
+/// [`Config::releases_url`](crate::Config) at a `file://` directory laid out like the GitHub
+/// releases to install from an offline bundle.
+#[derive(Clone, Debug, Default)]
+pub struct LocalDownloader {
+    bodies: HashMap<Url, Vec<u8>>,
+}
+
+impl LocalDownloader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves `body` for `url`.
+    pub fn insert(&mut self, url: Url, body: impl Into<Vec<u8>>) -> &mut Self {
+        self.bodies.insert(url, body.into());
+        self
+    }
+
+    fn get(&self, url: &Url) -> Result<Vec<u8>, YlemVmError> {
+        if let Some(body) = self.bodies.get(url) {
+            return Ok(body.clone());
+        }
+        if url.scheme() != "file" {
+            return Err(not_found(url));
+        }
+        let path = url.to_file_path().map_err(|_| not_found(url))?;
+        match fs::read(path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(not_found(url)),
+            res => Ok(res?),
+        }
+    }
+}
+
+impl Downloader for LocalDownloader {
+    fn download<'a>(&'a self, url: &'a Url, _timeout: Duration) -> DownloadFuture<'a> {
+        Box::pin(async move { self.get(url) })
+    }
+}
+
+impl BlockingDownloader for LocalDownloader {
+    fn download(&self, url: &Url, _timeout: Duration) -> Result<Vec<u8>, YlemVmError> {
+        self.get(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_downloader_serves_files_and_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("list.json"), b"{}").unwrap();
+        let base = Url::from_directory_path(dir.path()).unwrap();
+
+        let mut downloader = LocalDownloader::new();
+        let memory = Url::parse("https://example.com/ylem").unwrap();
+        downloader.insert(memory.clone(), b"ylem".to_vec());
+
+        let timeout = Duration::from_secs(1);
+        let get = |url: &Url| BlockingDownloader::download(&downloader, url, timeout);
+        assert_eq!(get(&memory).unwrap(), b"ylem");
+        assert_eq!(get(&base.join("list.json").unwrap()).unwrap(), b"{}");
+        for missing in [
+            base.join("missing").unwrap(),
+            Url::parse("https://example.com/missing").unwrap(),
+        ] {
+            assert!(matches!(
+                get(&missing),
+                Err(YlemVmError::UnsuccessfulResponse(_, StatusCode::NOT_FOUND))
+            ));
+        }
+    }
+}
@@ -1,33 +1,92 @@
-use reqwest::StatusCode;
+use http::StatusCode;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use thiserror::Error;
 use url::Url;
 
+use crate::platform::Platform;
+
 /// Error types from the yvm_lib crate.
 #[derive(Debug, Error)]
 pub enum YlemVmError {
     #[error("YVM global version not set")]
     GlobalVersionNotSet,
+    #[error("No previous global version to switch back to")]
+    NoPreviousGlobalVersion,
     #[error("Unknown version provided")]
     UnknownVersion,
-    #[error("Unsupported version {0} for platform {1}")]
-    UnsupportedVersion(String, String),
+    #[error(
+        "Ylem {version} has no build for {platform}, only for {}",
+        .available.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    UnsupportedVersion {
+        version: semver::Version,
+        platform: Platform,
+        /// The platforms with a build of the version.
+        available: Vec<Platform>,
+    },
     #[error("Version {0} not installed")]
     VersionNotInstalled(String),
+    #[error(
+        "No version matches {spec}, installed versions: {}",
+        format_versions(.installed)
+    )]
+    NoMatchingVersion {
+        spec: String,
+        installed: Vec<semver::Version>,
+    },
     #[error("Checksum mismatch for version {version}: expected: {expected}, actual: {actual}")]
     ChecksumMismatch {
         version: String,
         expected: String,
         actual: String,
     },
-    #[error("Install step for ylem version {0} timed out after {1} seconds")]
-    Timeout(String, u64),
+    #[error("The release list has no checksum for version {0}")]
+    MissingChecksum(String),
+    #[error("Install step for ylem version {0} timed out after {} seconds", .1.as_secs_f64())]
+    Timeout(String, Duration),
+    #[error("Unknown config key {0}")]
+    UnknownConfigKey(String),
+    #[error("Invalid value {value:?} for config key {key}: {reason}")]
+    InvalidConfigValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+    #[error("Credentials {0} contain a control character, which can't be sent")]
+    InvalidCredentials(String),
+    #[error("Invalid config file {0}: {1}")]
+    InvalidConfigFile(std::path::PathBuf, String),
+    #[error(
+        "The data directory {} has layout version {found}, but this yvm only supports up to {supported}, please upgrade yvm",
+        .path.display()
+    )]
+    UnsupportedLayout {
+        path: std::path::PathBuf,
+        found: String,
+        supported: u32,
+    },
+    #[error("Release tag {0} is not a version, release lists locate builds by their version, e.g. 1.1.2")]
+    InvalidReleaseTag(String),
     #[error("Unable to patch ylem binary for nixos. stdout: {0}. stderr: {1}")]
     CouldNotPatchForNixOs(String, String),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+    #[error("Could not {op} {}", .path.display())]
+    Io {
+        /// What yvm was doing, e.g. `create directory`.
+        op: &'static str,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[cfg(feature = "reqwest")]
     #[error(transparent)]
     ReqwestError(#[from] reqwest::Error),
     #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
     SemverError(#[from] semver::Error),
     #[error(transparent)]
     UrlError(#[from] url::ParseError),
@@ -35,7 +94,32 @@ pub enum YlemVmError {
     UnsuccessfulResponse(Url, StatusCode),
     #[error("Unsupported Operating System {0}")]
     UnsupportedOs(String),
-    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
     #[error(transparent)]
     ZipError(#[from] zip::result::ZipError),
 }
+
+/// Attaches the failed operation and its path to I/O errors, see [`YlemVmError::Io`].
+pub(crate) trait IoContext<T> {
+    fn io_context(self, op: &'static str, path: &Path) -> Result<T, YlemVmError>;
+}
+
+impl<T> IoContext<T> for std::io::Result<T> {
+    fn io_context(self, op: &'static str, path: &Path) -> Result<T, YlemVmError> {
+        self.map_err(|source| YlemVmError::Io {
+            op,
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+fn format_versions(versions: &[semver::Version]) -> String {
+    if versions.is_empty() {
+        return "none".to_string();
+    }
+    versions
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,319 @@
+//! The audit log of the changes made to a data directory, for shared machines where several people
+//! install versions and switch the global one.
+//!
+//! Every install, removal and change of the global version appends a line of JSON to
+//! `history.jsonl`. Once the log grows beyond [`MAX_HISTORY_SIZE`] it is moved to
+//! `history.jsonl.1`, replacing the previous one, so that the history keeps between one and two
+//! times that size. Writers and readers lock `.locks/history.lock`, which unlike the history
+//! itself is never moved.
+
+use fs2::FileExt;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{error::IoContext, lock::LOCKS_DIR, YlemVmError, YVM_DATA_DIR};
+
+/// Name of the file holding the history of a data directory.
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// Name of the file holding the older history, after rotating [`HISTORY_FILE`].
+const ROTATED_HISTORY_FILE: &str = "history.jsonl.1";
+
+/// Name of the file in the locks directory that is locked while accessing the history.
+const HISTORY_LOCK_FILE: &str = "history.lock";
+
+/// Size beyond which the history is rotated.
+const MAX_HISTORY_SIZE: u64 = 1024 * 1024;
+
+/// Whether a data directory entry holds the history.
+pub(crate) fn is_history_file(name: &str) -> bool {
+    name == HISTORY_FILE || name == ROTATED_HISTORY_FILE
+}
+
+/// Derive path to the file holding the history of the yvm data directory.
+pub fn history_path() -> PathBuf {
+    YVM_DATA_DIR.join(HISTORY_FILE)
+}
+
+/// A change to the data directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Install,
+    Remove,
+    Use,
+    Unset,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Operation::Install => "install",
+            Operation::Remove => "remove",
+            Operation::Use => "use",
+            Operation::Unset => "unset",
+        };
+        f.write_str(s)
+    }
+}
+
+/// An entry of the history.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// When the change was made, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Who made the change.
+    pub user: String,
+    pub operation: Operation,
+    /// The version installed, removed or set as the global version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<Version>,
+    /// The global version before a `use` or `unset`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Version>,
+    /// The URL an installed build was downloaded from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The hex encoded SHA-256 checksum of an installed build.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl HistoryEntry {
+    /// An entry of the current user making the change now.
+    pub(crate) fn new(operation: Operation, version: Option<&Version>) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            user: current_user(),
+            operation,
+            version: version.cloned(),
+            previous: None,
+            url: None,
+            checksum: None,
+        }
+    }
+}
+
+/// The user running yvm. Under sudo, the user who ran sudo.
+fn current_user() -> String {
+    ["SUDO_USER", "USER", "USERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|user| !user.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Appends an entry to the history of the data directory `root`.
+pub(crate) fn record(root: &Path, entry: &HistoryEntry) -> Result<(), YlemVmError> {
+    record_in(root, entry, MAX_HISTORY_SIZE)
+}
+
+fn record_in(root: &Path, entry: &HistoryEntry, max_size: u64) -> Result<(), YlemVmError> {
+    let path = root.join(HISTORY_FILE);
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    // other yvm processes append to the same file, it's opened once locked as it may be rotated
+    // meanwhile
+    let _lock = lock_history(root, true)?;
+    let mut file = open_history(&path)?;
+    if file.metadata().io_context("read", &path)?.len() >= max_size {
+        let rotated = root.join(ROTATED_HISTORY_FILE);
+        fs::rename(&path, &rotated).io_context("rotate", &path)?;
+        file = open_history(&path)?;
+    }
+    file.write_all(&line).io_context("write", &path)
+}
+
+/// Locks the history of the data directory `root` until the returned file is closed, exclusively
+/// for writing it or shared for reading it.
+fn lock_history(root: &Path, exclusive: bool) -> Result<fs::File, YlemVmError> {
+    let locks_dir = root.join(LOCKS_DIR);
+    fs::create_dir_all(&locks_dir).io_context("create directory", &locks_dir)?;
+    let path = locks_dir.join(HISTORY_LOCK_FILE);
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .io_context("create lock file", &path)?;
+    if exclusive {
+        file.lock_exclusive().io_context("lock", &path)?;
+    } else {
+        FileExt::lock_shared(&file).io_context("lock", &path)?;
+    }
+    Ok(file)
+}
+
+fn open_history(path: &Path) -> Result<fs::File, YlemVmError> {
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .io_context("open", path)
+}
+
+/// Reads the history of the yvm data directory, oldest entry first. Lines that are not valid
+/// entries, e.g. of a write that was interrupted, are skipped.
+pub fn history() -> Result<Vec<HistoryEntry>, YlemVmError> {
+    read_history(&YVM_DATA_DIR)
+}
+
+pub(crate) fn read_history(root: &Path) -> Result<Vec<HistoryEntry>, YlemVmError> {
+    // not to read the rotated file before and the current one after a rotation
+    let _lock = lock_history(root, false)?;
+    let mut entries = Vec::new();
+    for name in [ROTATED_HISTORY_FILE, HISTORY_FILE] {
+        let path = root.join(name);
+        let content = match fs::read_to_string(&path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            content => content.io_context("read", &path)?,
+        };
+        entries.extend(
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok()),
+        );
+    }
+    Ok(entries)
+}
+
+/// Returns the global version before the last change of the global version, to switch back to it.
+/// Returns None if there was none, or the global version was never changed.
+pub fn previous_global_version() -> Result<Option<Version>, YlemVmError> {
+    Ok(previous_in(&read_history(&YVM_DATA_DIR)?))
+}
+
+fn previous_in(entries: &[HistoryEntry]) -> Option<Version> {
+    entries
+        .iter()
+        .rev()
+        // using the global version again doesn't change it
+        .filter(|entry| entry.previous != entry.version)
+        .find(|entry| matches!(entry.operation, Operation::Use | Operation::Unset))
+        .and_then(|entry| entry.previous.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        operation: Operation,
+        version: Option<Version>,
+        previous: Option<Version>,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            previous,
+            ..HistoryEntry::new(operation, version.as_ref())
+        }
+    }
+
+    #[test]
+    fn records_entries() {
+        let root = tempfile::tempdir().unwrap();
+        assert_eq!(read_history(root.path()).unwrap(), vec![]);
+
+        let install = HistoryEntry {
+            url: Some("https://example.com/ylem".to_string()),
+            checksum: Some("00ff".to_string()),
+            ..entry(Operation::Install, Some(Version::new(1, 1, 2)), None)
+        };
+        let unset = entry(Operation::Unset, None, Some(Version::new(1, 1, 2)));
+        record(root.path(), &install).unwrap();
+        record(root.path(), &unset).unwrap();
+        // an interrupted write
+        let mut file = open_history(&root.path().join(HISTORY_FILE)).unwrap();
+        file.write_all(b"{\"timestamp\":").unwrap();
+
+        assert_eq!(read_history(root.path()).unwrap(), vec![install, unset]);
+    }
+
+    #[test]
+    fn rotates_history() {
+        let root = tempfile::tempdir().unwrap();
+        let entries = (0..5)
+            .map(|patch| entry(Operation::Install, Some(Version::new(1, 1, patch)), None))
+            .collect::<Vec<_>>();
+        let size = serde_json::to_vec(&entries[0]).unwrap().len() as u64 + 1;
+        // two entries fit before rotating
+        for entry in &entries {
+            record_in(root.path(), entry, 2 * size).unwrap();
+        }
+
+        let read = |name: &str| {
+            fs::read_to_string(root.path().join(name))
+                .unwrap()
+                .lines()
+                .count()
+        };
+        assert_eq!(read(ROTATED_HISTORY_FILE), 2);
+        assert_eq!(read(HISTORY_FILE), 1);
+        // the oldest entries are gone
+        assert_eq!(read_history(root.path()).unwrap(), entries[2..]);
+    }
+
+    #[test]
+    fn locks_history_across_rotations() {
+        let root = tempfile::tempdir().unwrap();
+        let entries = (0..40)
+            .map(|patch| entry(Operation::Install, Some(Version::new(1, 1, patch)), None))
+            .collect::<Vec<_>>();
+        let size = serde_json::to_vec(&entries[0]).unwrap().len() as u64 + 1;
+        // rotating every 10 entries, no entry of concurrent writers gets lost in a rotation
+        std::thread::scope(|scope| {
+            for chunk in entries.chunks(10) {
+                let root = root.path();
+                scope.spawn(move || {
+                    for entry in chunk {
+                        record_in(root, entry, 10 * size).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert_eq!(read_history(root.path()).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn finds_previous_global_version() {
+        let (v1, v2) = (Version::new(1, 1, 1), Version::new(1, 1, 2));
+        assert_eq!(previous_in(&[]), None);
+
+        let mut entries = vec![
+            entry(Operation::Use, Some(v1.clone()), None),
+            entry(Operation::Use, Some(v2.clone()), Some(v1.clone())),
+            entry(Operation::Install, Some(Version::new(1, 0, 3)), None),
+        ];
+        assert_eq!(previous_in(&entries), Some(v1.clone()));
+        entries.push(entry(Operation::Unset, None, Some(v2.clone())));
+        assert_eq!(previous_in(&entries), Some(v2));
+        entries.truncate(1);
+        assert_eq!(previous_in(&entries), None);
+    }
+
+    #[test]
+    fn serializes_entries_as_json_lines() {
+        let entry = HistoryEntry {
+            timestamp: 1700000000,
+            user: "alice".to_string(),
+            ..entry(
+                Operation::Use,
+                Some(Version::new(1, 1, 2)),
+                Some(Version::new(1, 1, 1)),
+            )
+        };
+        assert_eq!(
+            serde_json::to_string(&entry).unwrap(),
+            r#"{"timestamp":1700000000,"user":"alice","operation":"use","version":"1.1.2","previous":"1.1.1"}"#
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Version-suffixed launchers such as `ylem-1.1.2` in the bin directory, for tools that hard-code
+//! the compiler they run.
+//!
+//! Launchers are links to the `ylem` shim, which runs the version in the name it was started by.
+//! Going through the shim records when the version was last used, so that
+//! [`prune`](crate::prune) doesn't remove versions only ever run through their launcher.
+
+use semver::Version;
+use std::{
+    env::consts::EXE_SUFFIX,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::IoContext, installed_versions, temp_path, version_dir, YlemVmError, YVM_DATA_DIR,
+};
+
+/// Derive path to the launcher of a specific Ylem version installed into the data directory
+/// `root`.
+pub(crate) fn launcher_path(root: &Path, version: &Version) -> PathBuf {
+    root.join("bin").join(format!("ylem-{version}{EXE_SUFFIX}"))
+}
+
+/// Returns the version of a launcher from its file name, or None if it is not a launcher, e.g.
+/// the `ylem` shim itself.
+fn parse_launcher(name: &str) -> Option<Version> {
+    let name = name.strip_suffix(EXE_SUFFIX).unwrap_or(name);
+    Version::parse(name.strip_prefix("ylem-")?).ok()
+}
+
+/// Whether a file in the bin directory is a launcher rather than e.g. the `ylem` shim.
+fn is_launcher(name: &str) -> bool {
+    name.ends_with(EXE_SUFFIX) && parse_launcher(name).is_some()
+}
+
+/// Returns the version to run if the shim was started through a launcher, from the path it was
+/// started by (`argv[0]`).
+pub fn launcher_version(argv0: &Path) -> Option<Version> {
+    parse_launcher(&argv0.file_name()?.to_string_lossy())
+}
+
+/// Links the launcher of an installed version to the `ylem` shim in the same bin directory. On
+/// unix the link is relative, so that data directories prepared for another machine keep working
+/// when moved. Where symlinks are unavailable the shim is hard linked, or copied across file
+/// systems. An existing launcher is replaced atomically, and left alone if there is no shim to
+/// link to; versions installed before the shim get their launcher from [`rehash`]. Returns the
+/// path of the launcher, if there is one.
+pub(crate) fn install_launcher(
+    root: &Path,
+    version: &Version,
+) -> Result<Option<PathBuf>, YlemVmError> {
+    let launcher = launcher_path(root, version);
+    let bin_dir = root.join("bin");
+    let shim = bin_dir.join(format!("ylem{EXE_SUFFIX}"));
+    if !shim.is_file() {
+        return Ok(None);
+    }
+
+    let tmp = temp_path(&bin_dir, &format!("ylem-{version}"));
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::symlink(format!("ylem{EXE_SUFFIX}"), &tmp)
+        .io_context("create link", &tmp)?;
+    #[cfg(not(target_family = "unix"))]
+    if fs::hard_link(&shim, &tmp).is_err() {
+        fs::copy(&shim, &tmp).io_context("copy the shim to", &tmp)?;
+    }
+    if let Err(err) = fs::rename(&tmp, &launcher) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err).io_context("replace", &launcher);
+    }
+
+    Ok(Some(launcher))
+}
+
+/// Removes the launcher of a version, if it has one.
+pub(crate) fn remove_launcher(root: &Path, version: &Version) -> Result<(), YlemVmError> {
+    remove_file_if_exists(&launcher_path(root, version))
+}
+
+fn remove_file_if_exists(path: &Path) -> Result<(), YlemVmError> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err).io_context("remove", path),
+        _ => Ok(()),
+    }
+}
+
+/// Rebuilds the launchers in [`bin_path`](crate::bin_path) from the installed versions, removing
+/// the ones of versions that are gone. Returns the paths of the launchers, none if the `ylem` shim
+/// is not linked there.
+pub fn rehash() -> Result<Vec<PathBuf>, YlemVmError> {
+    rehash_in(&YVM_DATA_DIR, &installed_versions()?)
+}
+
+fn rehash_in(root: &Path, versions: &[Version]) -> Result<Vec<PathBuf>, YlemVmError> {
+    // a version without its binary would get a dangling launcher
+    let versions: Vec<&Version> = versions
+        .iter()
+        .filter(|version| {
+            version_dir(root, version)
+                .join(format!("ylem-{version}"))
+                .is_file()
+        })
+        .collect();
+
+    let bin_dir = root.join("bin");
+    if bin_dir.exists() {
+        for entry in fs::read_dir(&bin_dir).io_context("read directory", &bin_dir)? {
+            let entry = entry.io_context("read directory", &bin_dir)?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // the launchers of the versions kept are replaced in place below
+            if is_launcher(&name)
+                && !parse_launcher(&name).map_or(false, |v| versions.contains(&&v))
+            {
+                fs::remove_file(entry.path()).io_context("remove", &entry.path())?;
+            }
+        }
+    }
+
+    let mut launchers = Vec::new();
+    for version in versions {
+        launchers.extend(install_launcher(root, version)?);
+    }
+    Ok(launchers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install_binary(root: &Path, version: &Version) {
+        let dir = version_dir(root, version);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("ylem-{version}")), version.to_string()).unwrap();
+    }
+
+    fn install_shim(root: &Path) -> PathBuf {
+        let shim = root.join("bin").join(format!("ylem{EXE_SUFFIX}"));
+        fs::create_dir_all(shim.parent().unwrap()).unwrap();
+        fs::write(&shim, "shim").unwrap();
+        shim
+    }
+
+    #[test]
+    fn launchers_run_the_shim() {
+        let root = tempfile::tempdir().unwrap();
+        let version = Version::new(1, 1, 2);
+        install_binary(root.path(), &version);
+        install_shim(root.path());
+
+        let launcher = install_launcher(root.path(), &version).unwrap().unwrap();
+        assert_eq!(
+            launcher,
+            root.path()
+                .join("bin")
+                .join(format!("ylem-1.1.2{EXE_SUFFIX}"))
+        );
+        assert_eq!(fs::read_to_string(&launcher).unwrap(), "shim");
+        assert_eq!(launcher_version(&launcher), Some(version.clone()));
+        // installing again replaces the launcher
+        install_launcher(root.path(), &version).unwrap();
+
+        // moving the data directory keeps the launcher working
+        let moved = tempfile::tempdir().unwrap();
+        let moved = moved.path().join("yvm");
+        fs::rename(root.path(), &moved).unwrap();
+        assert_eq!(
+            fs::read_to_string(launcher_path(&moved, &version)).unwrap(),
+            "shim"
+        );
+
+        remove_launcher(&moved, &version).unwrap();
+        assert!(launcher_path(&moved, &version).symlink_metadata().is_err());
+        remove_launcher(&moved, &version).unwrap();
+    }
+
+    #[test]
+    fn launchers_need_the_shim() {
+        let root = tempfile::tempdir().unwrap();
+        let version = Version::new(1, 1, 2);
+        install_binary(root.path(), &version);
+
+        assert_eq!(install_launcher(root.path(), &version).unwrap(), None);
+        let launcher = launcher_path(root.path(), &version);
+        assert!(launcher.symlink_metadata().is_err());
+
+        // a failed relink keeps the launcher
+        let shim = install_shim(root.path());
+        install_launcher(root.path(), &version).unwrap();
+        fs::remove_file(shim).unwrap();
+        assert_eq!(install_launcher(root.path(), &version).unwrap(), None);
+        assert!(launcher.symlink_metadata().is_ok());
+        assert!(rehash_in(root.path(), &[version]).unwrap().is_empty());
+        assert!(launcher.symlink_metadata().is_ok());
+        // no temporary links are left behind
+        assert_eq!(fs::read_dir(root.path().join("bin")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn rehash_rebuilds_launchers() {
+        let root = tempfile::tempdir().unwrap();
+        let (installed, missing, removed) = (
+            Version::new(1, 1, 2),
+            Version::new(1, 1, 1),
+            Version::new(1, 0, 3),
+        );
+        let shim = install_shim(root.path());
+        install_binary(root.path(), &removed);
+        install_launcher(root.path(), &removed).unwrap();
+        fs::remove_dir_all(version_dir(root.path(), &removed)).unwrap();
+        install_binary(root.path(), &installed);
+        fs::create_dir_all(version_dir(root.path(), &missing)).unwrap();
+
+        let launchers = rehash_in(root.path(), &[missing.clone(), installed.clone()]).unwrap();
+        assert_eq!(launchers, vec![launcher_path(root.path(), &installed)]);
+        assert!(launcher_path(root.path(), &removed)
+            .symlink_metadata()
+            .is_err());
+        assert!(launcher_path(root.path(), &missing)
+            .symlink_metadata()
+            .is_err());
+        assert!(shim.exists());
+    }
+
+    #[test]
+    fn recognizes_launchers() {
+        assert!(is_launcher(&format!("ylem-1.1.2{EXE_SUFFIX}")));
+        assert!(!is_launcher(&format!("ylem{EXE_SUFFIX}")));
+        assert!(!is_launcher(&format!("ylem-nightly{EXE_SUFFIX}")));
+
+        for argv0 in ["ylem-1.1.2", "/home/alice/.yvm/bin/ylem-1.1.2"] {
+            assert_eq!(
+                launcher_version(Path::new(argv0)),
+                Some(Version::new(1, 1, 2))
+            );
+        }
+        assert_eq!(launcher_version(Path::new("/usr/bin/ylem")), None);
+        assert_eq!(launcher_version(Path::new("ylem-nightly")), None);
+    }
+}
@@ -0,0 +1,288 @@
+//! Versions of the data directory layout, and the migrations upgrading older layouts.
+//!
+//! The layout version is recorded in the `.layout-version` file of the data directory. Data
+//! directories without it were created before layouts were versioned, and have version 0. Every
+//! migration is idempotent, so that a migration interrupted half-way is simply run again.
+
+use fs2::FileExt;
+use semver::Version;
+use std::{
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{
+    current::link_current, error::IoContext, launcher, lock, version_dir, YlemVmError,
+    GLOBAL_VERSION_FILE,
+};
+
+/// Name of the file recording the layout version of a data directory.
+pub(crate) const LAYOUT_VERSION_FILE: &str = ".layout-version";
+
+/// The layout version this version of yvm creates.
+pub const LAYOUT_VERSION: u32 = 3;
+
+/// Upgrades the layout from the previous version to `to`.
+struct Migration {
+    to: u32,
+    migrate: fn(&Path) -> Result<(), YlemVmError>,
+}
+
+const MIGRATIONS: [Migration; 3] = [
+    Migration {
+        to: 1,
+        migrate: remove_legacy_locks,
+    },
+    Migration {
+        to: 2,
+        migrate: link_launchers,
+    },
+    Migration {
+        to: 3,
+        migrate: link_global_version,
+    },
+];
+
+/// Upgrades the layout of the data directory `root` to [`LAYOUT_VERSION`]. Fails with
+/// [`YlemVmError::UnsupportedLayout`] without changing anything if the layout is newer.
+pub(crate) fn migrate(root: &Path) -> Result<(), YlemVmError> {
+    let path = root.join(LAYOUT_VERSION_FILE);
+    let mut marker = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(&path)
+        .io_context("open", &path)?;
+    // another yvm may be migrating the same directory, wait for it and go on from where it got
+    marker.lock_exclusive().io_context("lock", &path)?;
+
+    let mut content = String::new();
+    marker
+        .read_to_string(&mut content)
+        .io_context("read", &path)?;
+    let content = content.trim();
+    let version = match content {
+        "" => 0,
+        content => content
+            .parse()
+            .ok()
+            .filter(|version| *version <= LAYOUT_VERSION)
+            .ok_or_else(|| YlemVmError::UnsupportedLayout {
+                path: root.to_path_buf(),
+                found: content.to_string(),
+                supported: LAYOUT_VERSION,
+            })?,
+    };
+
+    for migration in MIGRATIONS.iter().filter(|m| m.to > version) {
+        (migration.migrate)(root)?;
+        // recorded after each migration, so that a failed one is resumed
+        marker
+            .set_len(0)
+            .and_then(|_| marker.seek(SeekFrom::Start(0)))
+            .and_then(|_| marker.write_all(migration.to.to_string().as_bytes()))
+            .io_context("write", &path)?;
+    }
+    Ok(())
+}
+
+/// Versions installed in the data directory `root` with their binary.
+fn installed_versions_in(root: &Path) -> Result<Vec<Version>, YlemVmError> {
+    let mut versions = vec![];
+    for entry in fs::read_dir(root).io_context("read directory", root)? {
+        let name = entry.io_context("read directory", root)?.file_name();
+        if let Ok(version) = Version::parse(&name.to_string_lossy()) {
+            if version_dir(root, &version)
+                .join(format!("ylem-{version}"))
+                .is_file()
+            {
+                versions.push(version);
+            }
+        }
+    }
+    versions.sort();
+    Ok(versions)
+}
+
+/// 1: install locks moved from `.lock-ylem-<version>` files at the top level into `.locks`.
+/// Legacy locks still held by an older yvm are left alone.
+fn remove_legacy_locks(root: &Path) -> Result<(), YlemVmError> {
+    for entry in fs::read_dir(root).io_context("read directory", root)? {
+        let entry = entry.io_context("read directory", root)?;
+        if lock::is_legacy_lock_file(&entry.file_name().to_string_lossy())
+            && !lock::is_held(&entry.path())?
+        {
+            match fs::remove_file(entry.path()) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(err).io_context("remove", &entry.path())
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 2: the bin directory holds a launcher per installed version, linked to the `ylem` shim.
+fn link_launchers(root: &Path) -> Result<(), YlemVmError> {
+    for version in installed_versions_in(root)? {
+        if launcher::launcher_path(root, &version)
+            .symlink_metadata()
+            .is_err()
+        {
+            launcher::install_launcher(root, &version)?;
+        }
+    }
+    Ok(())
+}
+
+/// 3: the `current` link points to the directory of the global version.
+fn link_global_version(root: &Path) -> Result<(), YlemVmError> {
+    let global = fs::read_to_string(root.join(GLOBAL_VERSION_FILE)).unwrap_or_default();
+    match Version::parse(global.trim()) {
+        Ok(version) if version_dir(root, &version).is_dir() => link_current(root, Some(&version)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        current::{read_current_link, CurrentLink},
+        launcher::launcher_path,
+    };
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    /// Copies the data directory of the given layout version from the fixtures.
+    fn fixture(name: &str) -> TempDir {
+        fn copy(from: &Path, to: &Path) {
+            fs::create_dir_all(to).unwrap();
+            for entry in fs::read_dir(from).unwrap() {
+                let entry = entry.unwrap();
+                let to = to.join(entry.file_name());
+                if entry.path().is_dir() {
+                    copy(&entry.path(), &to);
+                } else {
+                    fs::copy(entry.path(), to).unwrap();
+                }
+            }
+        }
+
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/layout");
+        let root = tempfile::tempdir().unwrap();
+        copy(&fixtures.join(name), root.path());
+        root
+    }
+
+    /// Runs a migration twice, to check that it is idempotent.
+    fn run_twice(migrate: fn(&Path) -> Result<(), YlemVmError>, root: &Path) {
+        migrate(root).unwrap();
+        migrate(root).unwrap();
+    }
+
+    fn layout_version(root: &Path) -> String {
+        fs::read_to_string(root.join(LAYOUT_VERSION_FILE)).unwrap()
+    }
+
+    fn entries(root: &Path) -> Vec<PathBuf> {
+        let mut entries = fs::read_dir(root)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn migration_1_removes_legacy_locks() {
+        let root = fixture("v0");
+        assert!(root.path().join(".lock-ylem-1.0.3").exists());
+        run_twice(remove_legacy_locks, root.path());
+        assert!(!root.path().join(".lock-ylem-1.0.3").exists());
+        assert!(root.path().join("1.0.3").join("ylem-1.0.3").exists());
+    }
+
+    #[test]
+    fn migration_2_links_launchers() {
+        let root = fixture("v1");
+        install_shim(root.path());
+        run_twice(link_launchers, root.path());
+        for version in [Version::new(1, 0, 3), Version::new(1, 1, 2)] {
+            let launcher = launcher_path(root.path(), &version);
+            assert_eq!(fs::read_to_string(launcher).unwrap(), "shim");
+        }
+        // the directory of a broken install gets no launcher
+        assert!(launcher_path(root.path(), &Version::new(1, 1, 0))
+            .symlink_metadata()
+            .is_err());
+    }
+
+    #[test]
+    fn migration_3_links_global_version() {
+        let root = fixture("v2");
+        run_twice(link_global_version, root.path());
+        assert_eq!(
+            read_current_link(root.path()).unwrap(),
+            CurrentLink::Version(Version::new(1, 1, 2))
+        );
+
+        // a global version that is not installed is not linked
+        let root = fixture("v2");
+        fs::write(root.path().join(GLOBAL_VERSION_FILE), "1.0.0").unwrap();
+        link_global_version(root.path()).unwrap();
+        assert_eq!(
+            read_current_link(root.path()).unwrap(),
+            CurrentLink::Missing
+        );
+    }
+
+    /// Links a fake `ylem` shim into the bin directory of `root`.
+    fn install_shim(root: &Path) -> PathBuf {
+        let shim = root
+            .join("bin")
+            .join(format!("ylem{}", std::env::consts::EXE_SUFFIX));
+        fs::create_dir_all(root.join("bin")).unwrap();
+        fs::write(&shim, "shim").unwrap();
+        shim
+    }
+
+    #[test]
+    fn migrates_to_latest_layout() {
+        for name in ["v0", "v1", "v2"] {
+            let root = fixture(name);
+            install_shim(root.path());
+            migrate(root.path()).unwrap();
+            assert_eq!(layout_version(root.path()), LAYOUT_VERSION.to_string());
+            migrate(root.path()).unwrap();
+
+            assert!(!root.path().join(".lock-ylem-1.0.3").exists());
+            assert!(launcher_path(root.path(), &Version::new(1, 1, 2)).exists());
+            assert!(root.path().join("current").join("ylem-1.1.2").exists());
+        }
+
+        let root = tempfile::tempdir().unwrap();
+        migrate(root.path()).unwrap();
+        assert_eq!(layout_version(root.path()), LAYOUT_VERSION.to_string());
+    }
+
+    #[test]
+    fn refuses_newer_layouts() {
+        for found in ["99", "garbage"] {
+            let root = fixture("v2");
+            fs::write(root.path().join(LAYOUT_VERSION_FILE), found).unwrap();
+            let before = entries(root.path());
+
+            let err = migrate(root.path()).unwrap_err();
+            assert!(
+                matches!(&err, YlemVmError::UnsupportedLayout { found: f, .. } if f == found),
+                "{err}"
+            );
+            assert_eq!(entries(root.path()), before);
+            assert_eq!(layout_version(root.path()), found);
+        }
+    }
+}
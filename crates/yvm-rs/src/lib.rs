@@ -1,28 +1,112 @@
 use once_cell::sync::Lazy;
 use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
 use sha2::Digest;
 
 use std::{
-    ffi::OsString,
     fs,
     io::{Cursor, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
 };
 
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 /// Use permissions extensions on unix
 #[cfg(target_family = "unix")]
 use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+use url::Url;
 
 mod error;
+use error::IoContext;
 pub use error::YlemVmError;
 
+mod blocking;
+pub use blocking::{
+    blocking_all_versions, blocking_install_release_with_downloader,
+    blocking_install_with_downloader, Yvm,
+};
+#[cfg(feature = "blocking")]
+pub use blocking::{
+    blocking_install, blocking_install_for, blocking_install_release, blocking_install_with_config,
+    blocking_use,
+};
+#[cfg(feature = "blocking")]
+pub use releases::blocking_fetch_releases;
+
+mod auth;
+pub use auth::{Auth, Credentials, Secret};
+
+mod config;
+pub use config::{
+    config_path, env_var_name, project_config_path, Config, CONFIG_KEYS, GLOBAL_ONLY_KEYS,
+    PROJECT_CONFIG_FILE_NAME,
+};
+
+mod current;
+pub use current::{current_link, current_path, CurrentLink};
+
+mod doctor;
+pub use doctor::{apply_fix, diagnose, ylems_before_shim, Finding, Fix, Severity};
+
+mod download;
+#[cfg(feature = "blocking")]
+pub use download::BlockingReqwestDownloader;
+#[cfg(feature = "reqwest")]
+pub use download::ReqwestDownloader;
+pub use download::{BlockingDownloader, DownloadFuture, Downloader, LocalDownloader};
+
+mod launcher;
+pub use launcher::{launcher_version, rehash};
+
+mod history;
+pub use history::{history, history_path, previous_global_version, HistoryEntry, Operation};
+
+mod layout;
+pub use layout::LAYOUT_VERSION;
+
+mod lock;
+pub use lock::{lock_files, locks_path, remove_stale_locks, LockInfo, LockOwner};
+
 mod platform;
-pub use platform::{platform, Platform};
+pub use platform::{emulation_available, libc, platform, EmulationFallback, Libc, Platform};
 
 mod releases;
-pub use releases::{all_releases, Releases};
+#[cfg(feature = "reqwest")]
+pub use releases::fetch_releases;
+pub use releases::{
+    all_releases, blocking_fetch_releases_with_downloader, fetch_releases_with_downloader,
+    list_file_name, platforms_with_version, Releases,
+};
+
+mod release_list;
+pub use release_list::{
+    artifact_platform, format_releases, github_releases_url, github_repo, list_platform,
+    merge_releases, releases_from_dir, releases_from_github, validate_releases, GeneratedReleases,
+    ListProblem,
+};
+
+mod prune;
+pub use prune::{
+    prune, referenced_versions, PruneOptions, Pruned, PrunedVersion, ReferencedVersions,
+};
+
+mod shell;
+pub use shell::{add_setup_line, remove_setup_line, Shell};
+
+mod version_spec;
+pub use version_spec::{resolve_version_spec, VersionSpec};
+
+#[cfg(test)]
+mod test_server;
+
+/// Environment variable that overrides the global version, e.g. for a single shell session.
+pub const YLEM_VERSION_ENV: &str = "YLEM_VERSION";
+
+/// Name of the file that pins the Ylem version used within a project directory.
+pub const VERSION_FILE_NAME: &str = ".ylem-version";
+
+/// Name of the file holding the global version in the data directory.
+const GLOBAL_VERSION_FILE: &str = ".global-version";
 
 pub static YVM_DATA_DIR: Lazy<PathBuf> = Lazy::new(|| {
     #[cfg(test)]
@@ -52,10 +136,10 @@ fn resolve_data_dir() -> PathBuf {
     }
 }
 
-/// The timeout to use for requests to the source
+/// The default timeout to use for requests to the source
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
 
-/// Version beyond which ylem binaries are not fully static, hence need to be patched for NixOS.
+/// Default version beyond which ylem binaries are not fully static, hence need to be patched for NixOS.
 static NIXOS_PATCH_REQ: Lazy<VersionReq> = Lazy::new(|| VersionReq::parse(">=0.7.6").unwrap());
 
 // Installer type that copies binary data to the appropriate ylem binary file:
@@ -66,24 +150,29 @@ struct Installer {
     version: Version,
     // binary data of the ylem executable
     binbytes: Vec<u8>,
+    // data directory the version is installed into
+    root: PathBuf,
+    // whether the binary has to be patched to run on this NixOS machine
+    patch_for_nixos: bool,
 }
 
 impl Installer {
     /// Installs the ylem version at the version specific destination and returns the path to the installed ylem file.
     fn install(&self) -> Result<PathBuf, YlemVmError> {
-        let version_path = version_path(self.version.to_string().as_str());
+        let version_path = version_dir(&self.root, &self.version);
         let ylem_path = version_path.join(format!("ylem-{}", self.version));
         // create ylem file.
-        let mut f = fs::File::create(&ylem_path)?;
+        let mut f = fs::File::create(&ylem_path).io_context("create", &ylem_path)?;
 
         #[cfg(target_family = "unix")]
-        f.set_permissions(Permissions::from_mode(0o777))?;
+        f.set_permissions(Permissions::from_mode(0o777))
+            .io_context("set permissions of", &ylem_path)?;
 
         // copy contents over
         let mut content = Cursor::new(&self.binbytes);
-        std::io::copy(&mut content, &mut f)?;
+        std::io::copy(&mut content, &mut f).io_context("write", &ylem_path)?;
 
-        if platform::is_nixos() && NIXOS_PATCH_REQ.matches(&self.version) {
+        if self.patch_for_nixos {
             patch_for_nixos(ylem_path)
         } else {
             Ok(ylem_path)
@@ -92,9 +181,8 @@ impl Installer {
 
     /// Extracts the ylem archive at the version specified destination and returns the path to the
     /// installed ylem binary.
-    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
     fn install_zip(&self) -> Result<PathBuf, YlemVmError> {
-        let version_path = version_path(self.version.to_string().as_str());
+        let version_path = version_dir(&self.root, &self.version);
         let ylem_path = version_path.join(format!("ylem-{}", self.version));
 
         // extract archive
@@ -103,7 +191,8 @@ impl Installer {
         archive.extract(version_path.as_path())?;
 
         // rename ylem binary
-        std::fs::rename(version_path.join("ylem.exe"), ylem_path.as_path())?;
+        std::fs::rename(version_path.join("ylem.exe"), ylem_path.as_path())
+            .io_context("rename ylem.exe to", &ylem_path)?;
 
         Ok(ylem_path)
     }
@@ -111,7 +200,11 @@ impl Installer {
 
 /// Patch the given binary to use the dynamic linker provided by nixos
 pub fn patch_for_nixos(bin: PathBuf) -> Result<PathBuf, YlemVmError> {
-    let output = Command::new("nix-shell")
+    run_patchelf(Path::new("nix-shell"), bin)
+}
+
+fn run_patchelf(nix_shell: &Path, bin: PathBuf) -> Result<PathBuf, YlemVmError> {
+    let output = Command::new(nix_shell)
         .arg("-p")
         .arg("patchelf")
         .arg("--run")
@@ -120,7 +213,7 @@ pub fn patch_for_nixos(bin: PathBuf) -> Result<PathBuf, YlemVmError> {
             bin.display()
         ))
         .output()
-        .expect("Failed to execute command");
+        .io_context("run", nix_shell)?;
 
     match output.status.success() {
         true => Ok(bin),
@@ -138,31 +231,129 @@ pub fn version_path(version: &str) -> PathBuf {
     version_path
 }
 
+/// Derive path to a specific Ylem version's directory inside the data directory `root`.
+fn version_dir(root: &Path, version: &Version) -> PathBuf {
+    root.join(version.to_string())
+}
+
 /// Derive path to YVM's global version file.
 pub fn global_version_path() -> PathBuf {
     let mut global_version_path = YVM_DATA_DIR.to_path_buf();
-    global_version_path.push(".global-version");
+    global_version_path.push(GLOBAL_VERSION_FILE);
     global_version_path
 }
 
+/// Derive path to the directory holding the `ylem` shim and the launchers of the installed
+/// versions, such as `ylem-1.1.2`, which is meant to be put on `PATH`.
+pub fn bin_path() -> PathBuf {
+    YVM_DATA_DIR.join("bin")
+}
+
 /// Reads the currently set global version for Ylem. Returns None if none has yet been set.
 pub fn current_version() -> Result<Option<Version>, YlemVmError> {
-    let v = fs::read_to_string(global_version_path().as_path())?;
+    let path = global_version_path();
+    let v = fs::read_to_string(&path).io_context("read", &path)?;
     Ok(Version::parse(v.trim_end_matches('\n').to_string().as_str()).ok())
 }
 
-/// Sets the provided version as the global version for Ylem.
-pub fn use_version(version: &Version) -> Result<(), YlemVmError> {
-    let mut v = fs::File::create(global_version_path().as_path())?;
-    v.write_all(version.to_string().as_bytes())?;
+/// Derive path to the file recording when a specific Ylem version was last run.
+fn last_used_path(version: &Version) -> PathBuf {
+    version_path(version.to_string().as_str()).join(".last-used")
+}
+
+/// Records that the provided version was just run. Called by the `ylem` shim.
+pub fn record_last_used(version: &Version) -> Result<(), YlemVmError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = last_used_path(version);
+    fs::write(&path, now.to_string()).io_context("write", &path)?;
     Ok(())
 }
 
-/// Unset the global version. This should be done if all versions are removed.
+/// Reads when the provided version was last run through the `ylem` shim. Returns None if it has
+/// never been run.
+pub fn last_used(version: &Version) -> Result<Option<SystemTime>, YlemVmError> {
+    let path = last_used_path(version);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let secs = fs::read_to_string(&path).io_context("read", &path)?;
+    Ok(secs
+        .trim()
+        .parse()
+        .ok()
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+}
+
+/// Details about how an installed version was obtained, stored in its version directory.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallMetadata {
+    /// The platform of the installed build. It differs from the machine's platform if the build
+    /// runs under emulation.
+    pub platform: Platform,
+    /// Name of the release artifact the build was downloaded from.
+    pub artifact: String,
+}
+
+/// Derive path to the file holding the [`InstallMetadata`] of a specific Ylem version installed
+/// into the data directory `root`.
+fn install_metadata_path(root: &Path, version: &Version) -> PathBuf {
+    version_dir(root, version).join(".install.json")
+}
+
+/// Reads how the provided version was installed. Returns None for versions installed by older
+/// versions of yvm, which did not record it.
+pub fn install_metadata(version: &Version) -> Result<Option<InstallMetadata>, YlemVmError> {
+    let path = install_metadata_path(&YVM_DATA_DIR, version);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let metadata = fs::read_to_string(&path).io_context("read", &path)?;
+    Ok(Some(serde_json::from_str(&metadata)?))
+}
+
+/// Reads the version the `ylem` shim should run. The `YLEM_VERSION` environment variable takes
+/// precedence over the global version.
+pub fn active_version() -> Result<Option<Version>, YlemVmError> {
+    match std::env::var(YLEM_VERSION_ENV) {
+        Ok(v) if !v.trim().is_empty() => Ok(Some(Version::parse(v.trim())?)),
+        _ => current_version(),
+    }
+}
+
+/// Sets the provided version as the global version for Ylem, and points the [`current_path`]
+/// link to it.
+pub fn use_version(version: &Version) -> Result<(), YlemVmError> {
+    set_global_version(&YVM_DATA_DIR, Some(version))
+}
+
+/// Unset the global version, removing the [`current_path`] link. This should be done if all
+/// versions are removed.
 pub fn unset_global_version() -> Result<(), YlemVmError> {
-    let mut v = fs::File::create(global_version_path().as_path())?;
-    v.write_all("".as_bytes())?;
-    Ok(())
+    set_global_version(&YVM_DATA_DIR, None)
+}
+
+fn set_global_version(root: &Path, version: Option<&Version>) -> Result<(), YlemVmError> {
+    let path = root.join(GLOBAL_VERSION_FILE);
+    let previous = fs::read_to_string(&path)
+        .ok()
+        .and_then(|v| Version::parse(v.trim()).ok());
+    let content = version.map(Version::to_string).unwrap_or_default();
+    let mut v = fs::File::create(&path).io_context("write", &path)?;
+    v.write_all(content.as_bytes()).io_context("write", &path)?;
+    current::link_current(root, version)?;
+
+    let operation = match version {
+        Some(_) => Operation::Use,
+        None => Operation::Unset,
+    };
+    let entry = HistoryEntry {
+        previous,
+        ..HistoryEntry::new(operation, version)
+    };
+    history::record(root, &entry)
 }
 
 /// Reads the list of Ylem versions that have been installed in the machine. The version list is
@@ -170,9 +361,9 @@ pub fn unset_global_version() -> Result<(), YlemVmError> {
 pub fn installed_versions() -> Result<Vec<Version>, YlemVmError> {
     let home_dir = YVM_DATA_DIR.to_path_buf();
     let mut versions = vec![];
-    for v in fs::read_dir(home_dir)? {
-        let v = v?;
-        if v.file_name() != OsString::from(".global-version".to_string()) {
+    for v in fs::read_dir(&home_dir).io_context("read directory", &home_dir)? {
+        let v = v.io_context("read directory", &home_dir)?;
+        if !is_reserved_entry(&v.file_name().to_string_lossy()) {
             versions.push(Version::parse(
                 v.path()
                     .file_name()
@@ -188,12 +379,100 @@ pub fn installed_versions() -> Result<Vec<Version>, YlemVmError> {
     Ok(versions)
 }
 
-/// Blocking version of [`all_versions`]
-#[cfg(feature = "blocking")]
-pub fn blocking_all_versions() -> Result<Vec<Version>, YlemVmError> {
-    Ok(releases::all_releases(platform::platform())?
-        .clone()
-        .into_versions())
+/// Whether the given data directory entry is used by yvm itself rather than an installed version.
+fn is_reserved_entry(name: &str) -> bool {
+    matches!(
+        name,
+        GLOBAL_VERSION_FILE | layout::LAYOUT_VERSION_FILE | "bin" | ".locks" | "config.toml"
+    ) || current::is_current_entry(name)
+        || history::is_history_file(name)
+        || lock::is_legacy_lock_file(name)
+}
+
+/// Returns the platform whose builds may be installed on `platform` when a version has no native
+/// build, according to the configured [`EmulationFallback`] policy.
+pub fn fallback_platform(platform: Platform, config: &Config) -> Option<Platform> {
+    let emulation = platform == platform::platform() && platform::emulation_available();
+    select_fallback(platform, config.emulation_fallback, emulation)
+}
+
+fn select_fallback(
+    platform: Platform,
+    policy: EmulationFallback,
+    emulation_available: bool,
+) -> Option<Platform> {
+    match policy {
+        EmulationFallback::Auto => platform.emulated().filter(|_| emulation_available),
+        EmulationFallback::Always => platform.emulated(),
+        EmulationFallback::Never => None,
+    }
+}
+
+/// Returns the platform whose build of the provided version gets installed on `platform`: the
+/// platform itself if it has a native build, otherwise its [`fallback_platform`]. Fails with
+/// [`YlemVmError::UnsupportedVersion`] if only other platforms have a build of the version.
+pub fn resolve_platform(
+    platform: Platform,
+    version: &Version,
+    config: &Config,
+) -> Result<Platform, YlemVmError> {
+    if releases::all_releases(platform)?
+        .releases
+        .contains_key(version)
+    {
+        return Ok(platform);
+    }
+    match fallback_platform(platform, config) {
+        Some(fallback)
+            if releases::all_releases(fallback)?
+                .releases
+                .contains_key(version) =>
+        {
+            Ok(fallback)
+        }
+        _ => {
+            let available = releases::platforms_with_version(version);
+            if available.is_empty() {
+                return Err(YlemVmError::UnknownVersion);
+            }
+            Err(YlemVmError::UnsupportedVersion {
+                version: version.clone(),
+                platform,
+                available,
+            })
+        }
+    }
+}
+
+/// Returns up to `count` of the `versions` closest to `version`, in ascending order. Versions are
+/// compared component by component, so `1.1.9` is closer to `1.1.1` than `1.2.0` is, and `1.1.9`
+/// is closer to `1.3.0` than `1.1.0` is.
+pub fn closest_versions(version: &Version, versions: &[Version], count: usize) -> Vec<Version> {
+    let distance = |v: &Version| {
+        let below = v < version;
+        let mut same = true;
+        [
+            (v.major, version.major),
+            (v.minor, version.minor),
+            (v.patch, version.patch),
+        ]
+        .map(|(a, b)| {
+            // past the first differing component, the versions nearest to `version` are the
+            // newest ones below it and the oldest ones above it
+            let distance = match (same, below) {
+                (true, _) => a.abs_diff(b),
+                (false, true) => u64::MAX - a,
+                (false, false) => a,
+            };
+            same &= a == b;
+            distance
+        })
+    };
+    let mut closest = versions.to_vec();
+    closest.sort_by_key(distance);
+    closest.truncate(count);
+    closest.sort();
+    closest
 }
 
 /// Fetches the list of all the available versions of Ylem. The list is platform dependent, so
@@ -204,147 +483,372 @@ pub async fn all_versions() -> Result<Vec<Version>, YlemVmError> {
         .into_versions())
 }
 
-/// Blocking version of [`install`]
-#[cfg(feature = "blocking")]
-pub fn blocking_install(version: &Version) -> Result<PathBuf, YlemVmError> {
-    setup_data_dir()?;
-
-    let artifacts = releases::all_releases(platform::platform())?;
-    let artifact = artifacts
-        .get_artifact(version)
-        .ok_or(YlemVmError::UnknownVersion)?;
-    let download_url =
-        releases::artifact_url(platform::platform(), version, artifact.to_string().as_str())?;
-
-    let checksum = artifacts
-        .get_checksum(version)
-        .unwrap_or_else(|| panic!("checksum not available: {:?}", version.to_string()));
-
-    let res = reqwest::blocking::Client::builder()
-        .timeout(REQUEST_TIMEOUT)
-        .build()
-        .expect("reqwest::Client::new()")
-        .get(download_url.clone())
-        .send()?;
-
-    if !res.status().is_success() {
-        return Err(YlemVmError::UnsuccessfulResponse(
-            download_url,
-            res.status(),
-        ));
-    }
-
-    let binbytes = res.bytes()?;
-    ensure_checksum(&binbytes, version, checksum)?;
+/// Installs the provided version of Ylem in the machine.
+///
+/// Returns the path to the ylem file.
+#[cfg(feature = "reqwest")]
+pub async fn install(version: &Version) -> Result<PathBuf, YlemVmError> {
+    install_for(platform::platform(), version, &YVM_DATA_DIR).await
+}
 
-    // lock file to indicate that installation of this ylem version will be in progress.
-    let lock_path = lock_file_path(version);
-    // wait until lock file is released, possibly by another parallel thread trying to install the
-    // same version of ylem.
-    let _lock = try_lock_file(lock_path)?;
+/// Installs the build of the provided version of Ylem for the given platform instead of the
+/// detected one into the data directory `root`, laid out the way [`version_path`] lays it out in
+/// [`YVM_DATA_DIR`]. This can pick the glibc build on a musl system with a compatibility layer,
+/// or provision another machine's data directory, in which case the binary is not patched for
+/// NixOS. Roots other than [`YVM_DATA_DIR`] only get the build, without a launcher or a history
+/// entry. If the platform has no build of the version, the build of its [`fallback_platform`] is
+/// installed. Only the global configuration applies, see [`Config::load_global`]; use
+/// [`install_with_config`] to pass another one.
+///
+/// Returns the path to the ylem file.
+#[cfg(feature = "reqwest")]
+pub async fn install_for(
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    install_with_config(&Config::load_global()?, platform, version, root).await
+}
 
-    do_install(
-        version.clone(),
-        binbytes.to_vec(),
-        artifact.to_string().as_str(),
+/// Same as [`install_for`], using the given configuration instead of loading it.
+#[cfg(feature = "reqwest")]
+pub async fn install_with_config(
+    config: &Config,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    install_with_downloader(
+        &ReqwestDownloader::for_config(config)?,
+        config,
+        platform,
+        version,
+        root,
     )
+    .await
 }
 
-/// Installs the provided version of Ylem in the machine.
+/// Same as [`install_with_config`], downloading with the given [`Downloader`].
+pub async fn install_with_downloader(
+    downloader: &dyn Downloader,
+    config: &Config,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    let pending = PendingInstall::resolve(config, platform, version, root)?;
+    install_pending(downloader, config, pending).await
+}
+
+/// Installs the provided version of Ylem from the given list of releases of `platform` into the
+/// data directory `root`, e.g. from a list fetched with [`fetch_releases`] from a mirror.
 ///
 /// Returns the path to the ylem file.
-pub async fn install(version: &Version) -> Result<PathBuf, YlemVmError> {
-    setup_data_dir()?;
+#[cfg(feature = "reqwest")]
+pub async fn install_release(
+    config: &Config,
+    releases: &Releases,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    let downloader = ReqwestDownloader::for_config(config)?;
+    install_release_with_downloader(&downloader, config, releases, platform, version, root).await
+}
 
-    let artifacts = releases::all_releases(platform::platform())?;
-    let artifact = artifacts
-        .releases
-        .get(version)
-        .ok_or(YlemVmError::UnknownVersion)?;
-    let download_url =
-        releases::artifact_url(platform::platform(), version, artifact.to_string().as_str())?;
-
-    let checksum = artifacts
-        .get_checksum(version)
-        .unwrap_or_else(|| panic!("checksum not available: {:?}", version.to_string()));
-
-    let res = reqwest::Client::builder()
-        .timeout(REQUEST_TIMEOUT)
-        .build()
-        .expect("reqwest::Client::new()")
-        .get(download_url.clone())
-        .send()
-        .await?;
-
-    if !res.status().is_success() {
-        return Err(YlemVmError::UnsuccessfulResponse(
-            download_url,
-            res.status(),
-        ));
+/// Same as [`install_release`], downloading with the given [`Downloader`].
+pub async fn install_release_with_downloader(
+    downloader: &dyn Downloader,
+    config: &Config,
+    releases: &Releases,
+    platform: Platform,
+    version: &Version,
+    root: &Path,
+) -> Result<PathBuf, YlemVmError> {
+    let pending = PendingInstall::new(config, releases, platform, version, root)?;
+    install_pending(downloader, config, pending).await
+}
+
+async fn install_pending(
+    downloader: &dyn Downloader,
+    config: &Config,
+    pending: PendingInstall,
+) -> Result<PathBuf, YlemVmError> {
+    let binbytes = match pending.cached(config) {
+        Some(binbytes) => binbytes,
+        None => {
+            let binbytes = downloader
+                .download(&pending.url, config.request_timeout)
+                .await?;
+            pending.downloaded(config, binbytes)?
+        }
+    };
+    pending.finish(config, binbytes)
+}
+
+/// Installs the provided version if it is not installed yet and sets it as the global version.
+#[cfg(feature = "reqwest")]
+pub async fn install_and_use(version: &Version) -> Result<(), YlemVmError> {
+    if !installed_versions()?.contains(version) {
+        install(version).await?;
     }
+    use_version(version)
+}
 
-    let binbytes = res.bytes().await?;
-    ensure_checksum(&binbytes, version, checksum)?;
+/// A release artifact about to be installed. The async and blocking installers share everything
+/// but the download.
+struct PendingInstall {
+    version: Version,
+    artifact: String,
+    url: Url,
+    checksum: Vec<u8>,
+    platform: Platform,
+    root: PathBuf,
+    patch_for_nixos: bool,
+}
 
-    // lock file to indicate that installation of this ylem version will be in progress.
-    let lock_path = lock_file_path(version);
-    // wait until lock file is released, possibly by another parallel thread trying to install the
-    // same version of ylem.
-    let _lock = try_lock_file(lock_path)?;
+impl PendingInstall {
+    /// Resolves the build to install on `platform`, see [`install_for`].
+    fn resolve(
+        config: &Config,
+        platform: Platform,
+        version: &Version,
+        root: &Path,
+    ) -> Result<Self, YlemVmError> {
+        let platform = resolve_platform(platform, version, config)?;
+        Self::new(
+            config,
+            releases::all_releases(platform)?,
+            platform,
+            version,
+            root,
+        )
+    }
 
-    do_install(
-        version.clone(),
-        binbytes.to_vec(),
-        artifact.to_string().as_str(),
-    )
+    fn new(
+        config: &Config,
+        releases: &Releases,
+        platform: Platform,
+        version: &Version,
+        root: &Path,
+    ) -> Result<Self, YlemVmError> {
+        let artifact = releases
+            .get_artifact(version)
+            .ok_or(YlemVmError::UnknownVersion)?;
+        // lists fetched from mirrors may lack the checksum
+        let checksum = releases
+            .get_checksum(version)
+            .ok_or_else(|| YlemVmError::MissingChecksum(version.to_string()))?;
+
+        if root == YVM_DATA_DIR.as_path() {
+            setup_data_dir()?;
+        } else {
+            fs::create_dir_all(root).io_context("create directory", root)?;
+        }
+
+        Ok(Self {
+            version: version.clone(),
+            artifact: artifact.clone(),
+            url: releases::artifact_url(&config.releases_url, version, artifact)?,
+            checksum,
+            platform,
+            root: root.to_path_buf(),
+            // binaries for other platforms are never run here
+            patch_for_nixos: platform == platform::platform()
+                && platform::is_nixos()
+                && config.nixos_patch.matches(version),
+        })
+    }
+
+    /// Whether the version is installed into [`YVM_DATA_DIR`] rather than another root, which only
+    /// holds the builds.
+    fn is_data_dir(&self) -> bool {
+        self.root == YVM_DATA_DIR.as_path()
+    }
+
+    /// Returns the artifact from the cache, if it is cached.
+    fn cached(&self, config: &Config) -> Option<Vec<u8>> {
+        read_cached(config, &self.version, &self.artifact, &self.checksum)
+    }
+
+    /// Verifies the checksum of the downloaded artifact and caches it.
+    fn downloaded(&self, config: &Config, binbytes: Vec<u8>) -> Result<Vec<u8>, YlemVmError> {
+        ensure_checksum(&binbytes, &self.version, self.checksum.clone())?;
+        write_cached(config, &self.version, &self.artifact, &binbytes)?;
+        Ok(binbytes)
+    }
+
+    fn finish(self, config: &Config, binbytes: Vec<u8>) -> Result<PathBuf, YlemVmError> {
+        // lock file to indicate that installation of this ylem version will be in progress.
+        // wait until lock file is released, possibly by another parallel thread trying to install
+        // the same version of ylem. installs into other roots are locked in the data directory.
+        let _lock = lock::try_lock_file(&YVM_DATA_DIR, &self.version, config.lock_timeout)?;
+
+        do_install(&self, binbytes)
+    }
 }
 
-fn do_install(
-    version: Version,
-    binbytes: Vec<u8>,
-    _artifact: &str,
-) -> Result<PathBuf, YlemVmError> {
-    let installer = {
-        setup_version(version.to_string().as_str())?;
+/// Derive path to the cached copy of a release artifact, if caching is configured.
+fn cache_path(config: &Config, version: &Version, artifact: &str) -> Option<PathBuf> {
+    config
+        .cache_dir
+        .as_ref()
+        .map(|dir| dir.join(version.to_string()).join(artifact))
+}
 
-        Installer { version, binbytes }
+/// Reads a cached release artifact. Copies that don't match the checksum are ignored.
+fn read_cached(
+    config: &Config,
+    version: &Version,
+    artifact: &str,
+    checksum: &[u8],
+) -> Option<Vec<u8>> {
+    let binbytes = fs::read(cache_path(config, version, artifact)?).ok()?;
+    ensure_checksum(&binbytes, version, checksum.to_vec())
+        .ok()
+        .map(|_| binbytes)
+}
+
+fn write_cached(
+    config: &Config,
+    version: &Version,
+    artifact: &str,
+    binbytes: &[u8],
+) -> Result<(), YlemVmError> {
+    if let Some(path) = cache_path(config, version, artifact) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).io_context("create directory", parent)?;
+        }
+        fs::write(&path, binbytes).io_context("write", &path)?;
+    }
+    Ok(())
+}
+
+fn do_install(pending: &PendingInstall, binbytes: Vec<u8>) -> Result<PathBuf, YlemVmError> {
+    let root = &pending.root;
+    let version = pending.version.clone();
+    let artifact = &pending.artifact;
+    setup_version(root, &version)?;
+    let metadata = InstallMetadata {
+        platform: pending.platform,
+        artifact: artifact.to_string(),
+    };
+    let metadata_path = install_metadata_path(root, &version);
+    fs::write(&metadata_path, serde_json::to_vec_pretty(&metadata)?)
+        .io_context("write", &metadata_path)?;
+
+    let installer = Installer {
+        version,
+        binbytes,
+        root: root.to_path_buf(),
+        patch_for_nixos: pending.patch_for_nixos,
     };
 
-    // Ylem versions <= 0.7.1 are .zip files for Windows only
-    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-    if _artifact.ends_with(".zip") {
-        return installer.install_zip();
+    // Ylem versions <= 0.7.1 are .zip files for Windows only, which may be installed into a root
+    // prepared on another platform
+    let ylem_path = if artifact.ends_with(".zip") {
+        installer.install_zip()?
+    } else {
+        installer.install()?
+    };
+    if !pending.is_data_dir() {
+        return Ok(ylem_path);
     }
+    launcher::install_launcher(root, &installer.version)?;
 
-    installer.install()
+    let entry = HistoryEntry {
+        url: Some(pending.url.to_string()),
+        checksum: Some(hex::encode(&pending.checksum)),
+        ..HistoryEntry::new(Operation::Install, Some(&installer.version))
+    };
+    history::record(root, &entry)?;
+    Ok(ylem_path)
 }
 
-/// Removes the provided version of Ylem from the machine.
+/// Removes the provided version of Ylem from the machine, along with its launcher.
 pub fn remove_version(version: &Version) -> Result<(), YlemVmError> {
-    fs::remove_dir_all(version_path(version.to_string().as_str()))?;
-    Ok(())
+    launcher::remove_launcher(&YVM_DATA_DIR, version)?;
+    let path = version_path(version.to_string().as_str());
+    fs::remove_dir_all(&path).io_context("remove directory", &path)?;
+    history::record(
+        &YVM_DATA_DIR,
+        &HistoryEntry::new(Operation::Remove, Some(version)),
+    )
+}
+
+/// Links (or copies, where symlinks are unavailable) the given `ylem` shim executable into
+/// [`bin_path`] and returns the path of the installed shim. An installed shim that is already up
+/// to date is left alone, otherwise it's replaced atomically, so that `ylem` is never missing for
+/// the shells running it meanwhile.
+pub fn install_shim(shim: &Path) -> Result<PathBuf, YlemVmError> {
+    let bin_dir = bin_path();
+    let target = bin_dir.join(format!("ylem{}", std::env::consts::EXE_SUFFIX));
+    #[cfg(target_family = "unix")]
+    let up_to_date = fs::read_link(&target).map_or(false, |linked| linked == shim);
+    #[cfg(not(target_family = "unix"))]
+    let up_to_date = matches!((fs::read(&target), fs::read(shim)), (Ok(a), Ok(b)) if a == b);
+    if up_to_date {
+        return Ok(target);
+    }
+
+    fs::create_dir_all(&bin_dir).io_context("create directory", &bin_dir)?;
+    let tmp = temp_path(&bin_dir, "ylem");
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::symlink(shim, &tmp).io_context("create link", &tmp)?;
+    #[cfg(not(target_family = "unix"))]
+    fs::copy(shim, &tmp).io_context("copy the shim to", &tmp)?;
+    if let Err(err) = fs::rename(&tmp, &target) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err).io_context("replace", &target);
+    }
+
+    Ok(target)
 }
 
-/// Setup YVM home directory.
+/// Derive a path in `dir` to create `name` at before renaming it into place. Concurrent updates,
+/// even from the same process, each get their own path.
+pub(crate) fn temp_path(dir: &Path, name: &str) -> PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    dir.join(format!(
+        ".{name}.{}.{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Removes the data directory with everything yvm created in it: the installed versions, the
+/// global version, the configuration, the install locks and the bin directory.
+pub fn implode() -> Result<(), YlemVmError> {
+    match fs::remove_dir_all(YVM_DATA_DIR.as_path()) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        res => res.io_context("remove directory", &YVM_DATA_DIR),
+    }
+}
+
+/// Setup YVM home directory, upgrading the layout of existing ones. Fails with
+/// [`YlemVmError::UnsupportedLayout`] if the directory was set up by a newer version of yvm.
 pub fn setup_data_dir() -> Result<PathBuf, YlemVmError> {
     // create $XDG_DATA_HOME or ~/.local/share/yvm, or fallback to ~/.yvm
     let yvm_dir = YVM_DATA_DIR.to_path_buf();
     if !yvm_dir.as_path().exists() {
-        fs::create_dir_all(yvm_dir.clone())?;
+        fs::create_dir_all(&yvm_dir).io_context("create directory", &yvm_dir)?;
     }
+    layout::migrate(&yvm_dir)?;
     // create $YVM/.global-version
     let mut global_version = YVM_DATA_DIR.to_path_buf();
-    global_version.push(".global-version");
+    global_version.push(GLOBAL_VERSION_FILE);
     if !global_version.as_path().exists() {
-        fs::File::create(global_version.as_path())?;
+        fs::File::create(&global_version).io_context("create", &global_version)?;
     }
     Ok(yvm_dir)
 }
 
-fn setup_version(version: &str) -> Result<(), YlemVmError> {
-    let v = version_path(version);
+fn setup_version(root: &Path, version: &Version) -> Result<(), YlemVmError> {
+    let v = version_dir(root, version);
     if !v.exists() {
-        fs::create_dir_all(v.as_path())?
+        fs::create_dir_all(&v).io_context("create directory", &v)?
     }
     Ok(())
 }
@@ -368,45 +872,13 @@ fn ensure_checksum(
     Ok(())
 }
 
-/// Creates the file and locks it exclusively, this will block if the file is currently locked
-fn try_lock_file(lock_path: PathBuf) -> Result<LockFile, YlemVmError> {
-    use fs2::FileExt;
-    let _lock_file = fs::OpenOptions::new()
-        .create(true)
-        .read(true)
-        .write(true)
-        .open(&lock_path)?;
-    _lock_file.lock_exclusive()?;
-    Ok(LockFile {
-        lock_path,
-        _lock_file,
-    })
-}
-
-/// Represents a lockfile that's removed once dropped
-struct LockFile {
-    _lock_file: fs::File,
-    lock_path: PathBuf,
-}
-
-impl Drop for LockFile {
-    fn drop(&mut self) {
-        let _ = fs::remove_file(&self.lock_path);
-    }
-}
-
-/// Returns the lockfile to use for a specific file
-fn lock_file_path(version: &Version) -> PathBuf {
-    YVM_DATA_DIR.join(format!(".lock-ylem-{version}"))
-}
-
 #[cfg(test)]
 mod tests {
     use crate::{
         platform::Platform,
-        releases::{all_releases, artifact_url},
+        releases::{all_releases, artifact_url, BuildInfo},
+        test_server::{ReleaseServer, Route},
     };
-    use rand::seq::SliceRandom;
     use reqwest::Url;
 
     use std::process::{Command, Stdio};
@@ -414,6 +886,7 @@ mod tests {
     use super::*;
 
     #[tokio::test]
+    #[allow(clippy::unnecessary_unwrap)]
     async fn test_data_dir_resolution() {
         let home_dir = dirs::home_dir().unwrap().join(".yvm");
         let data_dir = dirs::data_dir();
@@ -425,13 +898,424 @@ mod tests {
         }
     }
 
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_install_shim() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let shim = dir.path().join("ylem");
+        fs::write(&shim, "shim").unwrap();
+        let target = install_shim(&shim).unwrap();
+        assert_eq!(fs::read_link(&target).unwrap(), shim);
+
+        // an up to date shim is not touched
+        let ino = target.symlink_metadata().unwrap().ino();
+        install_shim(&shim).unwrap();
+        assert_eq!(target.symlink_metadata().unwrap().ino(), ino);
+
+        let other = dir.path().join("other");
+        fs::write(&other, "other").unwrap();
+        install_shim(&other).unwrap();
+        assert_eq!(fs::read_link(&target).unwrap(), other);
+        // no temporary links are left behind
+        assert!(fs::read_dir(bin_path()).unwrap().all(|entry| !entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(".ylem.")));
+    }
+
+    #[test]
+    fn test_last_used() {
+        let version = Version::new(0, 0, 29);
+        setup_data_dir().unwrap();
+        setup_version(&YVM_DATA_DIR, &version).unwrap();
+        assert_eq!(last_used(&version).unwrap(), None);
+
+        record_last_used(&version).unwrap();
+        let used = last_used(&version).unwrap().unwrap();
+        assert!(SystemTime::now().duration_since(used).unwrap() < Duration::from_secs(60));
+
+        remove_version(&version).unwrap();
+    }
+
+    #[test]
+    fn test_select_fallback() {
+        use crate::EmulationFallback::*;
+        assert_eq!(
+            select_fallback(Platform::MacOsAarch64, Auto, true),
+            Some(Platform::MacOsAmd64)
+        );
+        assert_eq!(select_fallback(Platform::MacOsAarch64, Auto, false), None);
+        assert_eq!(
+            select_fallback(Platform::LinuxAarch64, Always, false),
+            Some(Platform::LinuxAmd64)
+        );
+        assert_eq!(select_fallback(Platform::LinuxAarch64, Never, true), None);
+        assert_eq!(select_fallback(Platform::LinuxAmd64, Always, true), None);
+    }
+
+    #[test]
+    fn test_resolve_platform() {
+        let version = Version::new(1, 1, 2);
+        for platform in [Platform::LinuxAmd64, Platform::MacOsAarch64] {
+            assert_eq!(
+                resolve_platform(platform, &version, &Config::default()).unwrap(),
+                platform
+            );
+        }
+        assert!(matches!(
+            resolve_platform(
+                Platform::LinuxAmd64,
+                &Version::new(0, 0, 1),
+                &Config::default()
+            ),
+            Err(YlemVmError::UnknownVersion)
+        ));
+
+        // 1.1.2 has no musl build
+        let err =
+            resolve_platform(Platform::LinuxAmd64Musl, &version, &Config::default()).unwrap_err();
+        assert!(matches!(
+            &err,
+            YlemVmError::UnsupportedVersion { platform: Platform::LinuxAmd64Musl, available, .. }
+                if available.contains(&Platform::LinuxAmd64) && !available.contains(&Platform::LinuxAmd64Musl)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Ylem 1.1.2 has no build for linux-amd64-musl, only for linux-amd64, linux-aarch64, macosx-amd64, macosx-aarch64, windows-amd64"
+        );
+    }
+
+    #[test]
+    fn test_closest_versions() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        let versions = [
+            v("0.9.0"),
+            v("1.0.3"),
+            v("1.1.0"),
+            v("1.1.9"),
+            v("1.2.0"),
+            v("2.0.0"),
+        ];
+        assert_eq!(
+            closest_versions(&v("1.1.1"), &versions, 3),
+            [v("1.1.0"), v("1.1.9"), v("1.2.0")]
+        );
+        assert_eq!(
+            closest_versions(&v("1.3.0"), &versions, 2),
+            [v("1.1.9"), v("1.2.0")]
+        );
+        assert_eq!(closest_versions(&v("3.0.0"), &versions, 1), [v("2.0.0")]);
+        assert!(closest_versions(&v("1.0.0"), &[], 3).is_empty());
+    }
+
+    /// An install of a fake build of `version` into `root`.
+    fn pending(
+        root: &Path,
+        version: &Version,
+        artifact: &str,
+        platform: Platform,
+    ) -> PendingInstall {
+        PendingInstall {
+            version: version.clone(),
+            artifact: artifact.to_string(),
+            url: Url::parse("https://example.com/ylem").unwrap(),
+            checksum: sha2::Sha256::digest(b"ylem").to_vec(),
+            platform,
+            root: root.to_path_buf(),
+            patch_for_nixos: false,
+        }
+    }
+
+    #[test]
+    fn test_install_metadata() {
+        let version = Version::new(0, 0, 28);
+        setup_data_dir().unwrap();
+        do_install(
+            &pending(
+                &YVM_DATA_DIR,
+                &version,
+                "ylem-darwin-x86_64",
+                Platform::MacOsAmd64,
+            ),
+            b"ylem".to_vec(),
+        )
+        .unwrap();
+        assert_eq!(
+            install_metadata(&version).unwrap(),
+            Some(InstallMetadata {
+                platform: Platform::MacOsAmd64,
+                artifact: "ylem-darwin-x86_64".to_string(),
+            })
+        );
+
+        let launcher = launcher::launcher_path(&YVM_DATA_DIR, &version);
+        remove_version(&version).unwrap();
+        assert!(launcher.symlink_metadata().is_err());
+        assert_eq!(install_metadata(&version).unwrap(), None);
+
+        // other tests share the history
+        let history = history()
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.version.as_ref() == Some(&version))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history
+                .iter()
+                .map(|entry| entry.operation)
+                .collect::<Vec<_>>(),
+            [Operation::Install, Operation::Remove]
+        );
+        assert_eq!(history[0].url.as_deref(), Some("https://example.com/ylem"));
+        assert_eq!(
+            history[0].checksum,
+            Some(hex::encode(sha2::Sha256::digest(b"ylem")))
+        );
+    }
+
+    #[test]
+    fn test_global_version_history() {
+        let root = tempfile::tempdir().unwrap();
+        let (v1, v2) = (Version::new(1, 1, 1), Version::new(1, 1, 2));
+        for version in [&v1, &v2] {
+            fs::create_dir_all(version_dir(root.path(), version)).unwrap();
+        }
+        set_global_version(root.path(), Some(&v1)).unwrap();
+        set_global_version(root.path(), Some(&v2)).unwrap();
+        set_global_version(root.path(), None).unwrap();
+
+        let history = fs::read_to_string(root.path().join("history.jsonl")).unwrap();
+        let history = history
+            .lines()
+            .map(|line| serde_json::from_str::<HistoryEntry>(line).unwrap())
+            .map(|entry| (entry.operation, entry.version, entry.previous))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history,
+            [
+                (Operation::Use, Some(v1.clone()), None),
+                (Operation::Use, Some(v2.clone()), Some(v1)),
+                (Operation::Unset, None, Some(v2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_artifact_cache() {
+        let cache = tempfile::tempdir().unwrap();
+        let version = Version::new(1, 1, 2);
+        let binbytes = b"ylem".to_vec();
+        let checksum = sha2::Sha256::digest(&binbytes).to_vec();
+
+        let mut config = Config::default();
+        write_cached(&config, &version, "ylem-linux-x86_64", &binbytes).unwrap();
+        assert_eq!(
+            read_cached(&config, &version, "ylem-linux-x86_64", &checksum),
+            None
+        );
+
+        config.cache_dir = Some(cache.path().to_path_buf());
+        write_cached(&config, &version, "ylem-linux-x86_64", &binbytes).unwrap();
+        assert_eq!(
+            read_cached(&config, &version, "ylem-linux-x86_64", &checksum),
+            Some(binbytes)
+        );
+        assert_eq!(
+            read_cached(&config, &version, "ylem-linux-x86_64", &[0; 32]),
+            None
+        );
+    }
+
+    /// Asserts that `res` failed to `op` on `path`, with the underlying I/O error as its source.
+    fn assert_io_error<T: std::fmt::Debug>(res: Result<T, YlemVmError>, op: &str, path: &Path) {
+        let err = res.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("Could not {op} {}", path.display())
+        );
+        assert!(
+            matches!(&err, YlemVmError::Io { path: p, .. } if p == path),
+            "{err:?}"
+        );
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_install_io_errors() {
+        let version = Version::new(1, 1, 2);
+        let install = |root: &Path| {
+            do_install(
+                &pending(root, &version, "ylem-linux-arm64", Platform::LinuxAarch64),
+                b"ylem".to_vec(),
+            )
+        };
+        let broken = |path: &Path, dir: bool| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            if dir {
+                fs::create_dir(path).unwrap();
+            } else {
+                fs::write(path, "").unwrap();
+            }
+        };
+
+        // a file in place of the data directory
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("yvm");
+        broken(&root, false);
+        assert_io_error(install(&root), "create directory", &root.join("1.1.2"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let metadata = install_metadata_path(dir.path(), &version);
+        broken(&metadata, true);
+        assert_io_error(install(dir.path()), "write", &metadata);
+
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("1.1.2").join("ylem-1.1.2");
+        broken(&binary, true);
+        assert_io_error(install(dir.path()), "create", &binary);
+
+        let dir = tempfile::tempdir().unwrap();
+        let launcher = launcher::launcher_path(dir.path(), &version);
+        broken(
+            &launcher.with_file_name(format!("ylem{}", std::env::consts::EXE_SUFFIX)),
+            false,
+        );
+        broken(&launcher, true);
+        assert_io_error(
+            launcher::install_launcher(dir.path(), &version),
+            "replace",
+            &launcher,
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let locks = dir.path().join(".locks");
+        broken(&locks, false);
+        assert_io_error(
+            lock::try_lock_file(dir.path(), &version, lock::LOCK_TIMEOUT).map(|_| ()),
+            "create directory",
+            &locks,
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        broken(&cache_dir, false);
+        let config = Config {
+            cache_dir: Some(cache_dir.clone()),
+            ..Default::default()
+        };
+        assert_io_error(
+            write_cached(&config, &version, "ylem-linux-arm64", b"ylem"),
+            "create directory",
+            &cache_dir.join("1.1.2"),
+        );
+
+        let nix_shell = dir.path().join("nix-shell");
+        assert_io_error(
+            run_patchelf(&nix_shell, dir.path().join("ylem")),
+            "run",
+            &nix_shell,
+        );
+    }
+
+    #[test]
+    fn test_global_version_io_errors() {
+        let version = Version::new(1, 1, 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        let global_version = dir.path().join(GLOBAL_VERSION_FILE);
+        fs::create_dir(&global_version).unwrap();
+        assert_io_error(
+            set_global_version(dir.path(), Some(&version)),
+            "write",
+            &global_version,
+        );
+
+        #[cfg(target_family = "unix")]
+        {
+            let dir = tempfile::tempdir().unwrap();
+            let current = dir.path().join("current");
+            fs::create_dir_all(current.join("ylem")).unwrap();
+            assert_io_error(
+                set_global_version(dir.path(), Some(&version)),
+                "replace link",
+                &current,
+            );
+
+            // a file in place of the link
+            let dir = tempfile::tempdir().unwrap();
+            let current = dir.path().join("current");
+            fs::write(&current, "").unwrap();
+            assert_io_error(
+                current::read_current_link(dir.path()),
+                "read link",
+                &current,
+            );
+        }
+
+        let version = Version::new(0, 0, 33);
+        setup_data_dir().unwrap();
+        assert_io_error(
+            remove_version(&version),
+            "remove directory",
+            &version_path("0.0.33"),
+        );
+    }
+
+    #[test]
+    fn test_install_into_root() {
+        fn files(dir: &Path, prefix: &Path, found: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let entry = entry.unwrap();
+                if entry.file_type().unwrap().is_dir() {
+                    files(&entry.path(), &prefix.join(entry.file_name()), found);
+                } else {
+                    found.push(prefix.join(entry.file_name()));
+                }
+            }
+        }
+
+        let root = tempfile::tempdir().unwrap();
+        let version = Version::new(0, 0, 36);
+        let path = pending(
+            root.path(),
+            &version,
+            "ylem-linux-arm64",
+            Platform::LinuxAarch64,
+        )
+        .finish(&Config::default(), b"ylem".to_vec())
+        .unwrap();
+        assert_eq!(path, root.path().join("0.0.36").join("ylem-0.0.36"));
+        assert_eq!(fs::read(path).unwrap(), b"ylem");
+
+        // the root only holds the build, without launchers, history or locks
+        let mut found = vec![];
+        files(root.path(), Path::new(""), &mut found);
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                Path::new("0.0.36").join(".install.json"),
+                Path::new("0.0.36").join("ylem-0.0.36"),
+            ]
+        );
+        assert!(!launcher::launcher_path(&YVM_DATA_DIR, &version).exists());
+        assert!(!history::read_history(&YVM_DATA_DIR)
+            .unwrap()
+            .iter()
+            .any(|entry| entry.version.as_ref() == Some(&version)));
+    }
+
     #[tokio::test]
+    #[allow(clippy::useless_format)]
     async fn test_artifact_url() {
         let version = Version::new(1, 1, 2);
         // https://github.com/core-coin/ylem/releases/download/1.1.2/ylem-darwin-x86_64
         let artifact = "ylem-linux-arm64";
         assert_eq!(
-            artifact_url(Platform::LinuxAarch64, &version, artifact).unwrap(),
+            artifact_url(&Config::default().releases_url, &version, artifact).unwrap(),
             Url::parse(&format!(
                 "https://github.com/core-coin/ylem/releases/download/1.1.2/ylem-linux-arm64"
             ))
@@ -439,30 +1323,279 @@ mod tests {
         )
     }
 
+    /// Fake ylem binary served by the [`ReleaseServer`].
+    const FAKE_YLEM: &[u8] =
+        b"#!/bin/sh\necho 'ylem, the ylem compiler commandline interface'\necho 'Version: 1.1.2'\n";
+
+    const FAKE_ARTIFACT: &str = "ylem-fake";
+
+    fn fake_releases(version: &Version, binbytes: &[u8]) -> Releases {
+        Releases {
+            builds: vec![BuildInfo {
+                version: version.clone(),
+                sha256: sha2::Sha256::digest(binbytes).to_vec(),
+            }],
+            releases: [(version.clone(), FAKE_ARTIFACT.to_string())].into(),
+        }
+    }
+
+    /// Serves the artifact of version 1.1.2 through the route and returns a config pointing at
+    /// the server.
+    fn serve(route: Route) -> (ReleaseServer, Config) {
+        let server = ReleaseServer::start([(format!("1.1.2/{FAKE_ARTIFACT}"), route)].into());
+        let config = Config {
+            releases_url: server.url().clone(),
+            ..Default::default()
+        };
+        (server, config)
+    }
+
     #[tokio::test]
     async fn test_install() {
-        let v = all_releases(platform()).unwrap();
-        let versions = v.releases.clone().into_keys().collect::<Vec<Version>>();
-        let rand_version = versions.choose(&mut rand::thread_rng()).unwrap();
-        assert!(install(rand_version).await.is_ok());
+        let version = Version::new(1, 1, 2);
+        let list = serde_json::to_vec(&fake_releases(&version, FAKE_YLEM)).unwrap();
+        let server = ReleaseServer::start(
+            [
+                ("list.json".to_string(), Route::Ok(list)),
+                (
+                    format!("1.1.2/{FAKE_ARTIFACT}"),
+                    Route::Ok(FAKE_YLEM.to_vec()),
+                ),
+            ]
+            .into(),
+        );
+        let config = Config {
+            releases_url: server.url().clone(),
+            ..Default::default()
+        };
+
+        let releases = fetch_releases(server.url().join("list.json").unwrap(), &config)
+            .await
+            .unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let path = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap();
+        assert_eq!(path, version_dir(root.path(), &version).join("ylem-1.1.2"));
+        assert_eq!(fs::read(path).unwrap(), FAKE_YLEM);
+    }
+
+    #[tokio::test]
+    async fn test_install_private_release() {
+        let version = Version::new(1, 1, 2);
+        let (server, mut config) = serve(Route::Authorized {
+            authorization: "Bearer secret".to_string(),
+            body: FAKE_YLEM.to_vec(),
+        });
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let root = tempfile::tempdir().unwrap();
+
+        let err = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            YlemVmError::UnsuccessfulResponse(_, reqwest::StatusCode::NOT_FOUND)
+        ));
+
+        config.set("auth-token", "secret").unwrap();
+        let path = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap();
+        assert_eq!(fs::read(path).unwrap(), FAKE_YLEM);
+        assert_eq!(server.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_install_offline_bundle() {
+        let version = Version::new(1, 1, 2);
+        let bundle = tempfile::tempdir().unwrap();
+        let list = serde_json::to_vec(&fake_releases(&version, FAKE_YLEM)).unwrap();
+        fs::write(bundle.path().join("list.json"), list).unwrap();
+        fs::create_dir(bundle.path().join("1.1.2")).unwrap();
+        fs::write(bundle.path().join("1.1.2").join(FAKE_ARTIFACT), FAKE_YLEM).unwrap();
+        let bundle_url = Url::from_directory_path(bundle.path()).unwrap();
+        let config = Config {
+            releases_url: bundle_url.clone(),
+            ..Default::default()
+        };
+
+        let downloader = LocalDownloader::new();
+        let releases = fetch_releases_with_downloader(
+            &downloader,
+            bundle_url.join("list.json").unwrap(),
+            &config,
+        )
+        .await
+        .unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let path = install_release_with_downloader(
+            &downloader,
+            &config,
+            &releases,
+            platform(),
+            &version,
+            root.path(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read(path).unwrap(), FAKE_YLEM);
+    }
+
+    #[test]
+    fn test_install_in_memory() {
+        let version = Version::new(1, 1, 2);
+        let config = Config::default();
+        let url = artifact_url(&config.releases_url, &version, FAKE_ARTIFACT).unwrap();
+        let mut downloader = LocalDownloader::new();
+        downloader.insert(url, FAKE_YLEM);
+        let root = tempfile::tempdir().unwrap();
+        let yvm = Yvm::with_downloader(config, downloader).root(root.path());
+
+        let path = yvm
+            .install_release(&fake_releases(&version, FAKE_YLEM), &version)
+            .unwrap();
+        assert_eq!(fs::read(path).unwrap(), FAKE_YLEM);
+        assert!(matches!(
+            yvm.install_release(
+                &fake_releases(&Version::new(1, 1, 1), FAKE_YLEM),
+                &Version::new(1, 1, 1)
+            ),
+            Err(YlemVmError::UnsuccessfulResponse(
+                _,
+                http::StatusCode::NOT_FOUND
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_install_checksum_mismatch() {
+        let version = Version::new(1, 1, 2);
+        let (_server, config) = serve(Route::Ok(b"tampered".to_vec()));
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let err = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, YlemVmError::ChecksumMismatch { .. }));
+        assert!(!version_dir(root.path(), &version).exists());
+    }
+
+    #[tokio::test]
+    async fn test_install_missing_checksum() {
+        let version = Version::new(1, 1, 2);
+        let (server, config) = serve(Route::Ok(FAKE_YLEM.to_vec()));
+        let root = tempfile::tempdir().unwrap();
+        let releases = Releases {
+            builds: vec![],
+            ..fake_releases(&version, FAKE_YLEM)
+        };
+        let err = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, YlemVmError::MissingChecksum(v) if v == "1.1.2"));
+        assert_eq!(server.requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_install_not_found() {
+        let version = Version::new(1, 1, 2);
+        let (_server, config) = serve(Route::Status(404));
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let err = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            YlemVmError::UnsuccessfulResponse(_, reqwest::StatusCode::NOT_FOUND)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_install_timeout() {
+        let version = Version::new(1, 1, 2);
+        let (_server, mut config) =
+            serve(Route::Delayed(Duration::from_secs(2), FAKE_YLEM.to_vec()));
+        config.request_timeout = Duration::from_millis(200);
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let err = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, YlemVmError::ReqwestError(e) if e.is_timeout()));
+    }
+
+    #[tokio::test]
+    async fn test_install_partial_response() {
+        let version = Version::new(1, 1, 2);
+        let (_server, config) = serve(Route::Partial {
+            body: FAKE_YLEM[..10].to_vec(),
+            length: FAKE_YLEM.len(),
+        });
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let res = install_release(&config, &releases, platform(), &version, root.path()).await;
+        assert!(res.is_err());
+        assert!(!version_dir(root.path(), &version).exists());
     }
 
     #[cfg(feature = "blocking")]
     #[test]
     fn blocking_test_install() {
-        let versions = crate::releases::all_releases(platform::platform())
-            .unwrap()
-            .clone()
-            .into_versions();
-        let rand_version = versions.choose(&mut rand::thread_rng()).unwrap();
-        assert!(blocking_install(rand_version).is_ok());
+        let version = Version::new(1, 1, 2);
+        let list = serde_json::to_vec(&fake_releases(&version, FAKE_YLEM)).unwrap();
+        let server = ReleaseServer::start(
+            [
+                ("list.json".to_string(), Route::Ok(list)),
+                (
+                    format!("1.1.2/{FAKE_ARTIFACT}"),
+                    Route::Ok(FAKE_YLEM.to_vec()),
+                ),
+            ]
+            .into(),
+        );
+        let root = tempfile::tempdir().unwrap();
+        let yvm = Yvm::with_config(Config {
+            releases_url: server.url().clone(),
+            ..Default::default()
+        })
+        .unwrap()
+        .root(root.path());
+
+        let releases = yvm
+            .fetch_releases(server.url().join("list.json").unwrap())
+            .unwrap();
+        let path = yvm.install_release(&releases, &version).unwrap();
+        assert_eq!(path, version_dir(root.path(), &version).join("ylem-1.1.2"));
+        assert_eq!(fs::read(path).unwrap(), FAKE_YLEM);
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn blocking_test_install_not_found() {
+        let version = Version::new(1, 1, 2);
+        let (_server, config) = serve(Route::Status(404));
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let err = blocking_install_release(&config, &releases, platform(), &version, root.path())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            YlemVmError::UnsuccessfulResponse(_, reqwest::StatusCode::NOT_FOUND)
+        ));
     }
 
     #[tokio::test]
+    #[cfg(target_family = "unix")]
     async fn test_version() {
-        let version = "1.1.2".parse().unwrap();
-        install(&version).await.unwrap();
-        let ylem_path = version_path(version.to_string().as_str()).join(format!("ylem-{version}"));
+        let version = Version::new(1, 1, 2);
+        let (_server, config) = serve(Route::Ok(FAKE_YLEM.to_vec()));
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let ylem_path = install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap();
         let output = Command::new(ylem_path)
             .arg("--version")
             .stdin(Stdio::piped())
@@ -476,12 +1609,16 @@ mod tests {
             .contains("1.1.2"));
     }
 
-    #[cfg(feature = "blocking")]
+    #[cfg(all(feature = "blocking", target_family = "unix"))]
     #[test]
     fn blocking_test_version() {
-        let version = "1.1.2".parse().unwrap();
-        blocking_install(&version).unwrap();
-        let ylem_path = version_path(version.to_string().as_str()).join(format!("ylem-{version}"));
+        let version = Version::new(1, 1, 2);
+        let (_server, config) = serve(Route::Ok(FAKE_YLEM.to_vec()));
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+        let ylem_path =
+            blocking_install_release(&config, &releases, platform(), &version, root.path())
+                .unwrap();
         let output = Command::new(ylem_path)
             .arg("--version")
             .stdin(Stdio::piped())
@@ -499,23 +1636,56 @@ mod tests {
     #[test]
     fn can_install_parallel() {
         let version: Version = "1.1.2".parse().unwrap();
-        let cloned_version = version.clone();
-        let t = std::thread::spawn(move || blocking_install(&cloned_version));
-        blocking_install(&version).unwrap();
+        let (server, config) = serve(Route::Delayed(
+            Duration::from_millis(100),
+            FAKE_YLEM.to_vec(),
+        ));
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+
+        let t = {
+            let (config, releases, version) = (config.clone(), releases.clone(), version.clone());
+            let root = root.path().to_path_buf();
+            std::thread::spawn(move || {
+                blocking_install_release(&config, &releases, platform(), &version, &root)
+            })
+        };
+        blocking_install_release(&config, &releases, platform(), &version, root.path()).unwrap();
         t.join().unwrap().unwrap();
+        assert_eq!(server.requests(), 2);
     }
 
     #[tokio::test(flavor = "multi_thread")]
     async fn can_install_parallel_async() {
         let version: Version = "1.1.2".parse().unwrap();
-        let cloned_version = version.clone();
-        let t = tokio::task::spawn(async move { install(&cloned_version).await });
-        install(&version).await.unwrap();
+        let (server, config) = serve(Route::Delayed(
+            Duration::from_millis(100),
+            FAKE_YLEM.to_vec(),
+        ));
+        let root = tempfile::tempdir().unwrap();
+        let releases = fake_releases(&version, FAKE_YLEM);
+
+        let t = {
+            let (config, releases, version) = (config.clone(), releases.clone(), version.clone());
+            let root = root.path().to_path_buf();
+            tokio::task::spawn(async move {
+                install_release(&config, &releases, platform(), &version, &root).await
+            })
+        };
+        install_release(&config, &releases, platform(), &version, root.path())
+            .await
+            .unwrap();
         t.await.unwrap().unwrap();
+        assert_eq!(server.requests(), 2);
+        assert_eq!(
+            fs::read(version_dir(root.path(), &version).join("ylem-1.1.2")).unwrap(),
+            FAKE_YLEM
+        );
     }
 
     // ensures we can download the latest native ylem for apple silicon
     #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "downloads from GitHub"]
     async fn can_download_latest_native_apple_silicon() {
         let latest: Version = "1.1.2".parse().unwrap();
 
@@ -523,7 +1693,7 @@ mod tests {
 
         let artifact = artifacts.releases.get(&latest).unwrap();
         let download_url = artifact_url(
-            Platform::MacOsAarch64,
+            &Config::default().releases_url,
             &latest,
             artifact.to_string().as_str(),
         )
@@ -538,22 +1708,39 @@ mod tests {
     }
 
     #[tokio::test]
-    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
     async fn can_install_windows_zip_release() {
-        let version = "1.1.2".parse().unwrap();
-        install(&version).await.unwrap();
-        let ylem_path =
-            version_path(version.to_string().as_str()).join(&format!("ylem-{}", version));
-        let output = Command::new(&ylem_path)
-            .arg("--version")
-            .stdin(Stdio::piped())
-            .stderr(Stdio::piped())
-            .stdout(Stdio::piped())
-            .output()
+        let version = Version::new(1, 1, 2);
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("ylem.exe", zip::write::FileOptions::default())
             .unwrap();
-
-        assert!(String::from_utf8_lossy(&output.stdout)
-            .as_ref()
-            .contains("1.1.2"));
+        zip.write_all(FAKE_YLEM).unwrap();
+        let archive = zip.finish().unwrap().into_inner();
+
+        let server = ReleaseServer::start(
+            [(
+                "1.1.2/ylem-fake.zip".to_string(),
+                Route::Ok(archive.clone()),
+            )]
+            .into(),
+        );
+        let config = Config {
+            releases_url: server.url().clone(),
+            ..Default::default()
+        };
+        let mut releases = fake_releases(&version, &archive);
+        releases
+            .releases
+            .insert(version.clone(), "ylem-fake.zip".to_string());
+        let root = tempfile::tempdir().unwrap();
+        let ylem_path = install_release(
+            &config,
+            &releases,
+            Platform::WindowsAmd64,
+            &version,
+            root.path(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read(ylem_path).unwrap(), FAKE_YLEM);
     }
 }
@@ -0,0 +1,373 @@
+use fs2::FileExt;
+use semver::Version;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::{IoContext, YlemVmError},
+    YVM_DATA_DIR,
+};
+
+/// How long to wait for another process installing the same version before giving up.
+pub(crate) const LOCK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Delay between two attempts to acquire a lock held by someone else.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Prefix of the lock files older versions of yvm created at the top of the data directory.
+const LEGACY_LOCK_PREFIX: &str = ".lock-ylem-";
+
+/// Name of the directory holding the install lock files inside a data directory.
+pub(crate) const LOCKS_DIR: &str = ".locks";
+
+/// Extension of the install lock files, see [`lock_file_path`].
+const LOCK_EXTENSION: &str = ".lock";
+
+/// Derive path to the directory holding the install lock files.
+pub fn locks_path() -> PathBuf {
+    YVM_DATA_DIR.join(LOCKS_DIR)
+}
+
+/// Returns the lockfile to use for a specific version installed into the data directory `root`
+fn lock_file_path(root: &Path, version: &Version) -> PathBuf {
+    root.join(LOCKS_DIR)
+        .join(format!("ylem-{version}{LOCK_EXTENSION}"))
+}
+
+/// Whether the given entry of the locks directory is an install lock file, rather than e.g. the
+/// lock of the history.
+fn is_install_lock_file(name: &str) -> bool {
+    name.starts_with("ylem-") && name.ends_with(LOCK_EXTENSION)
+}
+
+/// Whether the given data directory entry is a lock file created by an older version of yvm.
+pub(crate) fn is_legacy_lock_file(name: &str) -> bool {
+    name.starts_with(LEGACY_LOCK_PREFIX)
+}
+
+/// The process holding an install lock, as recorded in the lock file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub hostname: String,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            hostname: current_hostname(),
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let mut lines = s.lines();
+        let pid = lines.next()?.trim().parse().ok()?;
+        let hostname = lines.next()?.trim().to_string();
+        Some(Self { pid, hostname })
+    }
+
+    /// Whether the owning process is known to have exited. Processes on other hosts can't be
+    /// checked and are assumed to be alive.
+    pub fn is_gone(&self) -> bool {
+        self.hostname == current_hostname() && !process_exists(self.pid)
+    }
+}
+
+/// An install lock file found in the data directory.
+#[derive(Clone, Debug)]
+pub struct LockInfo {
+    pub path: PathBuf,
+    /// The recorded owner, if the lock file contains one.
+    pub owner: Option<LockOwner>,
+    /// Whether nobody holds the lock anymore, so the file can be removed. A lock that is held is
+    /// never stale, even if its recorded owner is gone, as the pid may have been reused.
+    pub stale: bool,
+}
+
+/// Creates the lock file for installing `version` into the data directory `root` and locks it
+/// exclusively. If the lock is held by someone else, waits for up to `timeout` before failing with
+/// [`YlemVmError::Timeout`].
+pub(crate) fn try_lock_file(
+    root: &Path,
+    version: &Version,
+    timeout: Duration,
+) -> Result<LockFile, YlemVmError> {
+    let lock_path = lock_file_path(root, version);
+    let locks_dir = root.join(LOCKS_DIR);
+    fs::create_dir_all(&locks_dir).io_context("create directory", &locks_dir)?;
+
+    let start = Instant::now();
+    let mut lock_file = loop {
+        let lock_file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&lock_path)
+            .io_context("create lock file", &lock_path)?;
+        match lock_file.try_lock_exclusive() {
+            // the previous holder may have removed the file after it was opened, then the lock
+            // is on an orphaned file and has to be taken on the new one
+            Ok(()) if is_linked(&lock_file, &lock_path)? => break lock_file,
+            Ok(()) => continue,
+            Err(err) if err.kind() != fs2::lock_contended_error().kind() => {
+                return Err(err).io_context("lock", &lock_path);
+            }
+            Err(_) => {}
+        }
+        if start.elapsed() >= timeout {
+            return Err(YlemVmError::Timeout(version.to_string(), timeout));
+        }
+        thread::sleep(LOCK_POLL_INTERVAL);
+    };
+
+    let owner = LockOwner::current();
+    lock_file
+        .set_len(0)
+        .and_then(|_| {
+            lock_file.write_all(format!("{}\n{}\n", owner.pid, owner.hostname).as_bytes())
+        })
+        .io_context("write lock file", &lock_path)?;
+
+    Ok(LockFile {
+        _lock_file: lock_file,
+        lock_path,
+    })
+}
+
+/// Represents a lockfile that's removed once dropped
+pub(crate) struct LockFile {
+    _lock_file: fs::File,
+    lock_path: PathBuf,
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        // removed while still locked, processes waiting for the lock notice it in `is_linked` and
+        // retry with a new file
+        let _ = fs::remove_file(&self.lock_path);
+    }
+}
+
+/// Whether `path` still names the locked `file`, i.e. the file wasn't removed or replaced.
+#[cfg(target_family = "unix")]
+fn is_linked(file: &fs::File, path: &Path) -> Result<bool, YlemVmError> {
+    use std::os::unix::fs::MetadataExt;
+    let locked = file.metadata().io_context("read metadata", path)?;
+    match fs::metadata(path) {
+        Ok(linked) => Ok(linked.dev() == locked.dev() && linked.ino() == locked.ino()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err).io_context("read metadata", path),
+    }
+}
+
+/// Whether `path` still names the locked `file`. Windows doesn't let a file that is open be
+/// replaced, so only its removal needs checking.
+#[cfg(not(target_family = "unix"))]
+fn is_linked(_file: &fs::File, path: &Path) -> Result<bool, YlemVmError> {
+    Ok(path.exists())
+}
+
+/// Lists the install lock files in the data directory, including the ones older versions of yvm
+/// left at its top level.
+pub fn lock_files() -> Result<Vec<LockInfo>, YlemVmError> {
+    let mut paths = vec![];
+    if locks_path().exists() {
+        let locks_dir = locks_path();
+        for entry in fs::read_dir(&locks_dir).io_context("read directory", &locks_dir)? {
+            let entry = entry.io_context("read directory", &locks_dir)?;
+            if is_install_lock_file(&entry.file_name().to_string_lossy()) {
+                paths.push(entry.path());
+            }
+        }
+    }
+    if YVM_DATA_DIR.exists() {
+        for entry in
+            fs::read_dir(YVM_DATA_DIR.as_path()).io_context("read directory", &YVM_DATA_DIR)?
+        {
+            let entry = entry.io_context("read directory", &YVM_DATA_DIR)?;
+            if is_legacy_lock_file(&entry.file_name().to_string_lossy()) {
+                paths.push(entry.path());
+            }
+        }
+    }
+    paths.sort();
+
+    let mut locks = Vec::with_capacity(paths.len());
+    for path in paths {
+        let owner = fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| LockOwner::parse(&s));
+        let stale = !is_held(&path)?;
+        locks.push(LockInfo { path, owner, stale });
+    }
+    Ok(locks)
+}
+
+/// Removes the lock files nobody holds anymore and returns their paths.
+pub fn remove_stale_locks() -> Result<Vec<PathBuf>, YlemVmError> {
+    let mut removed = vec![];
+    for lock in lock_files()?.into_iter().filter(|lock| lock.stale) {
+        if remove_lock_file(&lock.path)? {
+            removed.push(lock.path);
+        }
+    }
+    Ok(removed)
+}
+
+/// Removes a lock file unless someone holds it. The lock is taken for the removal, so that it
+/// can't be acquired in between. Returns whether the file was removed.
+pub(crate) fn remove_lock_file(path: &Path) -> Result<bool, YlemVmError> {
+    let file = match fs::OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err).io_context("open lock file", path),
+    };
+    match file.try_lock_exclusive() {
+        Ok(()) if is_linked(&file, path)? => {
+            fs::remove_file(path).io_context("remove lock file", path)?;
+            FileExt::unlock(&file).io_context("unlock", path)?;
+            Ok(true)
+        }
+        Ok(()) => Ok(false),
+        Err(err) if err.kind() == fs2::lock_contended_error().kind() => Ok(false),
+        Err(err) => Err(err).io_context("lock", path),
+    }
+}
+
+pub(crate) fn is_held(path: &Path) -> Result<bool, YlemVmError> {
+    let file = match fs::OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err).io_context("open lock file", path),
+    };
+    match file.try_lock_exclusive() {
+        Ok(()) => {
+            FileExt::unlock(&file).io_context("unlock", path)?;
+            Ok(false)
+        }
+        Err(err) if err.kind() == fs2::lock_contended_error().kind() => Ok(true),
+        Err(err) => Err(err).io_context("lock", path),
+    }
+}
+
+fn current_hostname() -> String {
+    hostname::get()
+        .map(|h| h.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(target_family = "unix")]
+fn process_exists(pid: u32) -> bool {
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => pid,
+        _ => return false,
+    };
+    // signal 0 performs the existence and permission checks without sending anything
+    let res = unsafe { libc::kill(pid, 0) };
+    res == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(target_family = "unix"))]
+fn process_exists(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_owner() {
+        let version = Version::new(0, 0, 30);
+        let lock = try_lock_file(&YVM_DATA_DIR, &version, LOCK_TIMEOUT).unwrap();
+        let owner = LockOwner::parse(&fs::read_to_string(&lock.lock_path).unwrap()).unwrap();
+        assert_eq!(owner, LockOwner::current());
+        assert!(!owner.is_gone());
+
+        let info = lock_files()
+            .unwrap()
+            .into_iter()
+            .find(|l| l.path == lock.lock_path)
+            .unwrap();
+        assert!(!info.stale);
+
+        drop(lock);
+        assert!(!lock_file_path(&YVM_DATA_DIR, &version).exists());
+    }
+
+    #[test]
+    fn times_out_when_held() {
+        let version = Version::new(0, 0, 31);
+        let _lock = try_lock_file(&YVM_DATA_DIR, &version, LOCK_TIMEOUT).unwrap();
+        let err = try_lock_file(&YVM_DATA_DIR, &version, Duration::from_millis(300))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Install step for ylem version 0.0.31 timed out after 0.3 seconds"
+        );
+    }
+
+    #[test]
+    fn removes_stale_locks() {
+        fs::create_dir_all(locks_path()).unwrap();
+        let path = lock_file_path(&YVM_DATA_DIR, &Version::new(0, 0, 32));
+        fs::write(&path, format!("{}\n{}\n", i32::MAX, current_hostname())).unwrap();
+
+        let info = lock_files()
+            .unwrap()
+            .into_iter()
+            .find(|l| l.path == path)
+            .unwrap();
+        assert!(info.stale);
+        assert!(info.owner.unwrap().is_gone());
+
+        assert!(remove_stale_locks().unwrap().contains(&path));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn keeps_held_locks_of_gone_owners() {
+        let version = Version::new(0, 0, 34);
+        let lock = try_lock_file(&YVM_DATA_DIR, &version, LOCK_TIMEOUT).unwrap();
+        // a recorded owner that looks gone, e.g. after a pid was reused on another container
+        fs::write(
+            &lock.lock_path,
+            format!("{}\n{}\n", i32::MAX, current_hostname()),
+        )
+        .unwrap();
+
+        let info = lock_files()
+            .unwrap()
+            .into_iter()
+            .find(|l| l.path == lock.lock_path)
+            .unwrap();
+        assert!(info.owner.unwrap().is_gone());
+        assert!(!info.stale);
+        assert!(!remove_lock_file(&lock.lock_path).unwrap());
+        assert!(lock.lock_path.exists());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn relocks_removed_lock_files() {
+        let version = Version::new(0, 0, 35);
+        let path = lock_file_path(&YVM_DATA_DIR, &version);
+        let lock = try_lock_file(&YVM_DATA_DIR, &version, LOCK_TIMEOUT).unwrap();
+        // a waiter that opened the file before the holder removed it
+        let orphaned = fs::File::open(&path).unwrap();
+        drop(lock);
+        orphaned.try_lock_exclusive().unwrap();
+        assert!(!is_linked(&orphaned, &path).unwrap());
+
+        let _lock = try_lock_file(&YVM_DATA_DIR, &version, Duration::ZERO).unwrap();
+        assert!(path.exists());
+    }
+}
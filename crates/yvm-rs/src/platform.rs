@@ -1,31 +1,55 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::Formatter;
+use std::path::Path;
 use std::str::FromStr;
 use std::{env, fmt};
 
 /// Types of supported platforms.
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Platform {
     /// Linux with x86
     LinuxAmd64,
+    /// Linux with x86 and musl libc
+    LinuxAmd64Musl,
     /// Linux with arm
     LinuxAarch64,
+    /// Linux with arm and musl libc
+    LinuxAarch64Musl,
+    /// Linux with risc-v
+    LinuxRiscv64,
     /// Mac with x86
     MacOsAmd64,
     /// Mac with arm(for m1 & m2)
     MacOsAarch64,
     /// Windows with x86
     WindowsAmd64,
+    /// Windows with arm
+    WindowsAarch64,
+    /// FreeBSD with x86
+    FreeBsdAmd64,
     Unsupported,
 }
 
+/// Types of C standard libraries a Linux system can use.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Libc {
+    Gnu,
+    Musl,
+}
+
 impl fmt::Display for Platform {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let s = match self {
             Platform::LinuxAmd64 => "linux-amd64",
+            Platform::LinuxAmd64Musl => "linux-amd64-musl",
             Platform::LinuxAarch64 => "linux-aarch64",
+            Platform::LinuxAarch64Musl => "linux-aarch64-musl",
+            Platform::LinuxRiscv64 => "linux-riscv64",
             Platform::MacOsAmd64 => "macosx-amd64",
             Platform::MacOsAarch64 => "macosx-aarch64",
             Platform::WindowsAmd64 => "windows-amd64",
+            Platform::WindowsAarch64 => "windows-aarch64",
+            Platform::FreeBsdAmd64 => "freebsd-amd64",
             Platform::Unsupported => "Unsupported-platform",
         };
         f.write_str(s)
@@ -38,47 +62,236 @@ impl FromStr for Platform {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "linux-amd64" => Ok(Platform::LinuxAmd64),
+            "linux-amd64-musl" => Ok(Platform::LinuxAmd64Musl),
             "linux-aarch64" => Ok(Platform::LinuxAarch64),
+            "linux-aarch64-musl" => Ok(Platform::LinuxAarch64Musl),
+            "linux-riscv64" => Ok(Platform::LinuxRiscv64),
             "macosx-amd64" => Ok(Platform::MacOsAmd64),
             "macosx-aarch64" => Ok(Platform::MacOsAarch64),
             "windows-amd64" => Ok(Platform::WindowsAmd64),
+            "windows-aarch64" => Ok(Platform::WindowsAarch64),
+            "freebsd-amd64" => Ok(Platform::FreeBsdAmd64),
             s => Err(format!("unsupported platform {s}")),
         }
     }
 }
 
+impl Serialize for Platform {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Platform {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Whether a build for an emulated architecture may be installed when a version has no native
+/// build for the current platform.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq)]
+pub enum EmulationFallback {
+    /// Fall back only if an emulator for the other architecture is detected.
+    #[default]
+    Auto,
+    /// Always fall back, e.g. if the emulator is not detected but known to be available.
+    Always,
+    /// Never fall back.
+    Never,
+}
+
+impl fmt::Display for EmulationFallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            EmulationFallback::Auto => "auto",
+            EmulationFallback::Always => "always",
+            EmulationFallback::Never => "never",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for EmulationFallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(EmulationFallback::Auto),
+            "always" => Ok(EmulationFallback::Always),
+            "never" => Ok(EmulationFallback::Never),
+            s => Err(format!(
+                "invalid emulation fallback {s}, expected auto, always or never"
+            )),
+        }
+    }
+}
+
 pub fn is_nixos() -> bool {
     std::path::Path::new("/etc/NIXOS").exists()
 }
 
+impl Platform {
+    /// All supported platforms.
+    pub const ALL: [Platform; 10] = [
+        Platform::LinuxAmd64,
+        Platform::LinuxAmd64Musl,
+        Platform::LinuxAarch64,
+        Platform::LinuxAarch64Musl,
+        Platform::LinuxRiscv64,
+        Platform::MacOsAmd64,
+        Platform::MacOsAarch64,
+        Platform::WindowsAmd64,
+        Platform::WindowsAarch64,
+        Platform::FreeBsdAmd64,
+    ];
+
+    /// Returns the C standard library binaries for this platform are linked against, if it
+    /// matters for the platform.
+    pub fn libc(&self) -> Option<Libc> {
+        match self {
+            Platform::LinuxAmd64 | Platform::LinuxAarch64 | Platform::LinuxRiscv64 => {
+                Some(Libc::Gnu)
+            }
+            Platform::LinuxAmd64Musl | Platform::LinuxAarch64Musl => Some(Libc::Musl),
+            _ => None,
+        }
+    }
+
+    /// Returns the platform whose binaries can run on this one through emulation, such as x86
+    /// binaries under Rosetta 2 on Apple silicon.
+    pub fn emulated(&self) -> Option<Platform> {
+        match self {
+            Platform::MacOsAarch64 => Some(Platform::MacOsAmd64),
+            Platform::LinuxAarch64 => Some(Platform::LinuxAmd64),
+            Platform::WindowsAarch64 => Some(Platform::WindowsAmd64),
+            _ => None,
+        }
+    }
+
+    /// Whether binaries of this platform can run on `host`: builds of the same system and
+    /// architecture whatever their libc, which compatibility layers bridge, and builds of the
+    /// platform `host` emulates.
+    pub fn runs_on(&self, host: Platform) -> bool {
+        let glibc = |platform: Platform| match platform {
+            Platform::LinuxAmd64Musl => Platform::LinuxAmd64,
+            Platform::LinuxAarch64Musl => Platform::LinuxAarch64,
+            platform => platform,
+        };
+        *self != Platform::Unsupported
+            && (glibc(*self) == glibc(host) || glibc(host).emulated() == Some(glibc(*self)))
+    }
+}
+
+/// Detects whether the current machine can run binaries of its [`Platform::emulated`] platform.
+pub fn emulation_available() -> bool {
+    match platform() {
+        Platform::MacOsAarch64 => Path::new("/Library/Apple/usr/share/rosetta/rosetta").exists(),
+        Platform::LinuxAarch64 => has_x86_64_binfmt(Path::new("/proc/sys/fs/binfmt_misc")),
+        // x86 emulation is built into Windows on arm
+        Platform::WindowsAarch64 => true,
+        _ => false,
+    }
+}
+
+/// Whether an enabled `binfmt_misc` handler for x86_64 binaries, e.g. from `qemu-user` or FEX, is
+/// registered in the given directory.
+fn has_x86_64_binfmt(binfmt_dir: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(binfmt_dir) else {
+        return false;
+    };
+    entries.flatten().any(|e| {
+        let name = e.file_name().to_string_lossy().to_lowercase();
+        (name.contains("x86_64") || name.contains("x86-64"))
+            && std::fs::read_to_string(e.path())
+                .map(|s| s.lines().next() == Some("enabled"))
+                .unwrap_or(false)
+    })
+}
+
 /// Read the current machine's platform.
 pub fn platform() -> Platform {
-    match (env::consts::OS, env::consts::ARCH) {
-        ("linux", "x86_64") => Platform::LinuxAmd64,
-        ("linux", "aarch64") => Platform::LinuxAarch64,
-        ("macos", "x86_64") => Platform::MacOsAmd64,
-        ("macos", "aarch64") => Platform::MacOsAarch64,
-        ("windows", "x86_64") => Platform::WindowsAmd64,
+    let libc = match env::consts::OS {
+        "linux" => libc(),
+        _ => Libc::Gnu,
+    };
+    match (env::consts::OS, env::consts::ARCH, libc) {
+        ("linux", "x86_64", Libc::Gnu) => Platform::LinuxAmd64,
+        ("linux", "x86_64", Libc::Musl) => Platform::LinuxAmd64Musl,
+        ("linux", "aarch64", Libc::Gnu) => Platform::LinuxAarch64,
+        ("linux", "aarch64", Libc::Musl) => Platform::LinuxAarch64Musl,
+        ("linux", "riscv64", Libc::Gnu) => Platform::LinuxRiscv64,
+        ("macos", "x86_64", _) => Platform::MacOsAmd64,
+        ("macos", "aarch64", _) => Platform::MacOsAarch64,
+        ("windows", "x86_64", _) => Platform::WindowsAmd64,
+        ("windows", "aarch64", _) => Platform::WindowsAarch64,
+        ("freebsd", "x86_64", _) => Platform::FreeBsdAmd64,
         _ => Platform::Unsupported,
     }
 }
 
+/// Detects the C standard library of the current Linux system from the dynamic loaders it ships.
+pub fn libc() -> Libc {
+    detect_libc(&[Path::new("/lib"), Path::new("/lib64")])
+}
+
+/// A system is considered musl based if it has a musl loader (`ld-musl-*`) but no glibc one
+/// (`ld-linux-*`), since glibc compatibility layers on musl systems may ship both.
+fn detect_libc(lib_dirs: &[&Path]) -> Libc {
+    let has_loader = |prefix: &str| {
+        lib_dirs.iter().any(|dir| {
+            std::fs::read_dir(dir)
+                .map(|entries| {
+                    entries
+                        .flatten()
+                        .any(|e| e.file_name().to_string_lossy().starts_with(prefix))
+                })
+                .unwrap_or(false)
+        })
+    };
+    if has_loader("ld-musl-") && !has_loader("ld-linux") {
+        Libc::Musl
+    } else {
+        Libc::Gnu
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "gnu"))]
     fn get_platform() {
         assert_eq!(platform(), Platform::LinuxAmd64);
     }
 
     #[test]
-    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "musl"))]
+    fn get_platform() {
+        assert_eq!(platform(), Platform::LinuxAmd64Musl);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "aarch64", target_env = "gnu"))]
     fn get_platform() {
         assert_eq!(platform(), Platform::LinuxAarch64);
     }
 
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "aarch64", target_env = "musl"))]
+    fn get_platform() {
+        assert_eq!(platform(), Platform::LinuxAarch64Musl);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "riscv64"))]
+    fn get_platform() {
+        assert_eq!(platform(), Platform::LinuxRiscv64);
+    }
+
     #[test]
     #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
     fn get_platform() {
@@ -96,4 +309,91 @@ mod tests {
     fn get_platform() {
         assert_eq!(platform(), Platform::WindowsAmd64);
     }
+
+    #[test]
+    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
+    fn get_platform() {
+        assert_eq!(platform(), Platform::WindowsAarch64);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "freebsd", target_arch = "x86_64"))]
+    fn get_platform() {
+        assert_eq!(platform(), Platform::FreeBsdAmd64);
+    }
+
+    #[test]
+    fn platform_roundtrip() {
+        for platform in Platform::ALL {
+            assert_eq!(platform.to_string().parse::<Platform>().unwrap(), platform);
+        }
+    }
+
+    #[test]
+    fn platform_serde() {
+        let json = serde_json::to_string(&Platform::MacOsAmd64).unwrap();
+        assert_eq!(json, "\"macosx-amd64\"");
+        assert_eq!(
+            serde_json::from_str::<Platform>(&json).unwrap(),
+            Platform::MacOsAmd64
+        );
+        assert!(serde_json::from_str::<Platform>("\"solaris\"").is_err());
+    }
+
+    #[test]
+    fn runs_on_hosts() {
+        assert!(Platform::LinuxAmd64.runs_on(Platform::LinuxAmd64));
+        assert!(Platform::LinuxAmd64Musl.runs_on(Platform::LinuxAmd64));
+        assert!(Platform::LinuxAmd64.runs_on(Platform::LinuxAarch64Musl));
+        assert!(Platform::MacOsAmd64.runs_on(Platform::MacOsAarch64));
+        assert!(!Platform::MacOsAarch64.runs_on(Platform::MacOsAmd64));
+        assert!(!Platform::WindowsAmd64.runs_on(Platform::LinuxAmd64));
+        assert!(!Platform::LinuxAarch64.runs_on(Platform::LinuxAmd64));
+        assert!(!Platform::Unsupported.runs_on(Platform::Unsupported));
+    }
+
+    #[test]
+    fn emulation_fallback_roundtrip() {
+        for policy in [
+            EmulationFallback::Auto,
+            EmulationFallback::Always,
+            EmulationFallback::Never,
+        ] {
+            assert_eq!(policy.to_string().parse::<EmulationFallback>(), Ok(policy));
+        }
+        assert!("sometimes".parse::<EmulationFallback>().is_err());
+    }
+
+    #[test]
+    fn detects_x86_64_binfmt() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("status"), "enabled\n").unwrap();
+        assert!(!has_x86_64_binfmt(dir.path()));
+        std::fs::write(
+            dir.path().join("qemu-x86_64"),
+            "disabled\ninterpreter /usr/bin/qemu-x86_64\n",
+        )
+        .unwrap();
+        assert!(!has_x86_64_binfmt(dir.path()));
+        std::fs::write(
+            dir.path().join("qemu-x86_64"),
+            "enabled\ninterpreter /usr/bin/qemu-x86_64\n",
+        )
+        .unwrap();
+        assert!(has_x86_64_binfmt(dir.path()));
+        assert!(!has_x86_64_binfmt(&dir.path().join("missing")));
+    }
+
+    #[test]
+    fn detects_libc() {
+        let musl = tempfile::tempdir().unwrap();
+        std::fs::write(musl.path().join("ld-musl-x86_64.so.1"), "").unwrap();
+        assert_eq!(detect_libc(&[musl.path()]), Libc::Musl);
+
+        let gnu = tempfile::tempdir().unwrap();
+        std::fs::write(gnu.path().join("ld-linux-x86-64.so.2"), "").unwrap();
+        assert_eq!(detect_libc(&[gnu.path()]), Libc::Gnu);
+        assert_eq!(detect_libc(&[musl.path(), gnu.path()]), Libc::Gnu);
+        assert_eq!(detect_libc(&[]), Libc::Gnu);
+    }
 }
@@ -0,0 +1,268 @@
+use semver::Version;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    current_version, error::IoContext, installed_versions, last_used, remove_version, version_path,
+    YlemVmError, VERSION_FILE_NAME,
+};
+
+/// Directories that are never searched for version files.
+const SKIPPED_DIRS: [&str; 3] = [".git", "node_modules", "target"];
+
+/// Options for [`prune`].
+#[derive(Clone, Debug, Default)]
+pub struct PruneOptions {
+    /// Project roots searched recursively for `.ylem-version` files. Versions referenced by these
+    /// files are kept.
+    pub roots: Vec<PathBuf>,
+    /// Number of the most recent installed versions to keep regardless of their use.
+    pub keep_latest: usize,
+    /// Only remove versions that were last used longer ago than this. Versions that were never
+    /// run through the `ylem` shim are aged by their install time.
+    pub older_than: Option<Duration>,
+    /// Report what would be removed without removing anything.
+    pub dry_run: bool,
+}
+
+/// A version removed (or, in a dry run, to be removed) by [`prune`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrunedVersion {
+    pub version: Version,
+    /// Disk space taken by the version's directory, in bytes.
+    pub size: u64,
+}
+
+/// The outcome of [`prune`].
+#[derive(Debug, Default)]
+pub struct Pruned {
+    pub versions: Vec<PrunedVersion>,
+    /// Directories under the project roots that could not be searched for version files, see
+    /// [`ReferencedVersions::unreadable`].
+    pub unreadable: Vec<YlemVmError>,
+}
+
+/// Removes installed versions that are neither the global version nor referenced by a
+/// `.ylem-version` file under any of the given project roots. Returns the pruned versions.
+pub fn prune(opts: &PruneOptions) -> Result<Pruned, YlemVmError> {
+    let installed = installed_versions()?;
+
+    let ReferencedVersions {
+        versions: mut keep,
+        unreadable,
+    } = referenced_versions(&opts.roots)?;
+    if let Some(global) = current_version()? {
+        keep.insert(global);
+    }
+
+    let cutoff = opts
+        .older_than
+        .and_then(|age| SystemTime::now().checked_sub(age));
+    let candidates = prune_candidates(&installed, &keep, opts.keep_latest, cutoff, |v| {
+        last_used(v).ok().flatten().or_else(|| {
+            fs::metadata(version_path(v.to_string().as_str()))
+                .and_then(|m| m.modified())
+                .ok()
+        })
+    });
+
+    let mut pruned = Vec::with_capacity(candidates.len());
+    for version in candidates {
+        let size = dir_size(&version_path(version.to_string().as_str()))?;
+        if !opts.dry_run {
+            remove_version(&version)?;
+        }
+        pruned.push(PrunedVersion { version, size });
+    }
+    Ok(Pruned {
+        versions: pruned,
+        unreadable,
+    })
+}
+
+/// Selects the versions to prune out of the sorted `installed` list.
+fn prune_candidates(
+    installed: &[Version],
+    keep: &HashSet<Version>,
+    keep_latest: usize,
+    cutoff: Option<SystemTime>,
+    last_used: impl Fn(&Version) -> Option<SystemTime>,
+) -> Vec<Version> {
+    let latest = installed.len().saturating_sub(keep_latest);
+    installed[..latest]
+        .iter()
+        .filter(|v| !keep.contains(v))
+        .filter(|v| match cutoff {
+            Some(cutoff) => last_used(v).map_or(true, |used| used < cutoff),
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// The versions pinned by `.ylem-version` files, see [`referenced_versions`].
+#[derive(Debug, Default)]
+pub struct ReferencedVersions {
+    pub versions: HashSet<Version>,
+    /// Directories and version files that could not be read, e.g. for lack of permissions. The
+    /// versions pinned in them are missing from `versions`.
+    pub unreadable: Vec<YlemVmError>,
+}
+
+/// Collects the versions pinned by `.ylem-version` files found under the given roots. Files that
+/// do not contain a valid version are ignored, and unreadable entries below the roots are skipped
+/// and reported in [`ReferencedVersions::unreadable`]. Fails if a root itself cannot be read.
+pub fn referenced_versions(roots: &[PathBuf]) -> Result<ReferencedVersions, YlemVmError> {
+    let mut found = ReferencedVersions::default();
+    for root in roots {
+        find_version_files(root, &mut found)?;
+    }
+    Ok(found)
+}
+
+fn find_version_files(dir: &Path, found: &mut ReferencedVersions) -> Result<(), YlemVmError> {
+    for entry in fs::read_dir(dir).io_context("read directory", dir)? {
+        let entry = entry.io_context("read directory", dir)?;
+        let file_type = entry
+            .file_type()
+            .io_context("read file type of", &entry.path())?;
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS.iter().any(|d| entry.file_name() == *d) {
+                if let Err(err) = find_version_files(&entry.path(), found) {
+                    found.unreadable.push(err);
+                }
+            }
+        } else if file_type.is_file() && entry.file_name() == VERSION_FILE_NAME {
+            let path = entry.path();
+            match fs::read_to_string(&path).io_context("read", &path) {
+                Ok(content) => {
+                    if let Ok(v) = Version::parse(content.trim()) {
+                        found.versions.insert(v);
+                    }
+                }
+                Err(err) => found.unreadable.push(err),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn dir_size(dir: &Path) -> Result<u64, YlemVmError> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir).io_context("read directory", dir)? {
+        let entry = entry.io_context("read directory", dir)?;
+        let metadata = entry
+            .metadata()
+            .io_context("read metadata of", &entry.path())?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(vs: &[&str]) -> Vec<Version> {
+        vs.iter().map(|v| Version::parse(v).unwrap()).collect()
+    }
+
+    #[test]
+    fn keeps_referenced_and_latest_versions() {
+        let installed = versions(&["1.0.0", "1.0.1", "1.1.0", "1.1.1", "1.1.2"]);
+        let keep = versions(&["1.0.1"]).into_iter().collect();
+        let pruned = prune_candidates(&installed, &keep, 2, None, |_| None);
+        assert_eq!(pruned, versions(&["1.0.0", "1.1.0"]));
+    }
+
+    #[test]
+    fn keeps_recently_used_versions() {
+        let installed = versions(&["1.0.0", "1.0.1", "1.1.0"]);
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let pruned =
+            prune_candidates(
+                &installed,
+                &HashSet::new(),
+                0,
+                Some(now - 7 * day),
+                |v| match v.patch {
+                    0 => Some(now - 30 * day),
+                    _ => Some(now - day),
+                },
+            );
+        assert_eq!(pruned, versions(&["1.0.0", "1.1.0"]));
+    }
+
+    #[test]
+    fn finds_version_files() {
+        let root = tempfile::tempdir().unwrap();
+        let nested = root.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.path().join("a").join(VERSION_FILE_NAME), "1.0.1\n").unwrap();
+        fs::write(nested.join(VERSION_FILE_NAME), "1.1.2").unwrap();
+        let skipped = root.path().join("target");
+        fs::create_dir_all(&skipped).unwrap();
+        fs::write(skipped.join(VERSION_FILE_NAME), "1.0.0").unwrap();
+        let invalid = root.path().join("c");
+        fs::create_dir_all(&invalid).unwrap();
+        fs::write(invalid.join(VERSION_FILE_NAME), "latest").unwrap();
+
+        let found = referenced_versions(&[root.path().to_path_buf()]).unwrap();
+        assert_eq!(
+            found.versions,
+            versions(&["1.0.1", "1.1.2"]).into_iter().collect()
+        );
+        assert!(found.unreadable.is_empty());
+    }
+
+    #[test]
+    fn skips_unreadable_version_files() {
+        let root = tempfile::tempdir().unwrap();
+        let nested = root.path().join("a");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join(VERSION_FILE_NAME), [0xff, 0xfe]).unwrap();
+        fs::write(root.path().join(VERSION_FILE_NAME), "1.0.1").unwrap();
+
+        let found = referenced_versions(&[root.path().to_path_buf()]).unwrap();
+        assert_eq!(found.versions, versions(&["1.0.1"]).into_iter().collect());
+        assert_eq!(found.unreadable.len(), 1);
+        assert!(referenced_versions(&[root.path().join("missing")]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_unreadable_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = tempfile::tempdir().unwrap();
+        let locked = root.path().join("a");
+        fs::create_dir_all(&locked).unwrap();
+        fs::write(root.path().join(VERSION_FILE_NAME), "1.0.1").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // root reads the directory regardless of its permissions
+        let readable = fs::read_dir(&locked).is_ok();
+
+        let found = referenced_versions(&[root.path().to_path_buf()]).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(found.versions, versions(&["1.0.1"]).into_iter().collect());
+        assert_eq!(found.unreadable.len(), usize::from(!readable));
+    }
+
+    #[test]
+    fn measures_dir_size() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("a"), [0u8; 10]).unwrap();
+        fs::write(dir.path().join("sub").join("b"), [0u8; 5]).unwrap();
+        assert_eq!(dir_size(dir.path()).unwrap(), 15);
+    }
+}
@@ -0,0 +1,552 @@
+//! Tooling for maintainers of release lists like the bundled ones in `list/`, e.g. for a fork of
+//! ylem.
+
+use semver::Version;
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use sha2::Digest;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+use crate::{
+    error::IoContext,
+    releases::{self, BuildInfo},
+    Config, Downloader, Platform, Releases, YlemVmError,
+};
+
+/// Returns the platform of a release artifact from its name, e.g. `ylem-linux-x86_64` or
+/// `ylem-windows-arm64.exe`.
+pub fn artifact_platform(artifact: &str) -> Option<Platform> {
+    let name = artifact
+        .strip_suffix(".exe")
+        .or_else(|| artifact.strip_suffix(".zip"))
+        .unwrap_or(artifact);
+    let target = name.strip_prefix("ylem-")?;
+    let (target, musl) = match target.strip_suffix("-musl") {
+        Some(target) => (target, true),
+        None => (target, false),
+    };
+    let (os, arch) = target.split_once('-')?;
+    let arch = match arch {
+        "x86_64" | "amd64" => "amd64",
+        "arm64" | "aarch64" => "aarch64",
+        arch => arch,
+    };
+    Some(match (os, arch, musl) {
+        ("linux", "amd64", false) => Platform::LinuxAmd64,
+        ("linux", "amd64", true) => Platform::LinuxAmd64Musl,
+        ("linux", "aarch64", false) => Platform::LinuxAarch64,
+        ("linux", "aarch64", true) => Platform::LinuxAarch64Musl,
+        ("linux", "riscv64", false) => Platform::LinuxRiscv64,
+        ("darwin" | "macos", "amd64", false) => Platform::MacOsAmd64,
+        ("darwin" | "macos", "aarch64", false) => Platform::MacOsAarch64,
+        ("windows", "amd64", false) => Platform::WindowsAmd64,
+        ("windows", "aarch64", false) => Platform::WindowsAarch64,
+        ("freebsd", "amd64", false) => Platform::FreeBsdAmd64,
+        _ => return None,
+    })
+}
+
+/// Release lists generated from release artifacts, see [`releases_from_dir`] and
+/// [`releases_from_github`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedReleases {
+    /// Releases of every platform, empty for platforms without artifacts.
+    pub releases: BTreeMap<Platform, Releases>,
+    /// Artifacts that were left out, because their platform is unknown or the platform already
+    /// has an artifact of the version.
+    pub skipped: Vec<String>,
+}
+
+impl Default for GeneratedReleases {
+    fn default() -> Self {
+        Self {
+            releases: Platform::ALL
+                .into_iter()
+                .map(|platform| (platform, Releases::default()))
+                .collect(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl GeneratedReleases {
+    /// Adds the artifact of a version, computing its checksum.
+    fn add(&mut self, version: &Version, artifact: &str, binbytes: &[u8]) {
+        let releases = artifact_platform(artifact)
+            .and_then(|platform| self.releases.get_mut(&platform))
+            .filter(|releases| !releases.releases.contains_key(version));
+        let Some(releases) = releases else {
+            self.skipped.push(artifact.to_string());
+            return;
+        };
+        releases
+            .releases
+            .insert(version.clone(), artifact.to_string());
+        releases.builds.push(BuildInfo {
+            version: version.clone(),
+            sha256: sha2::Sha256::digest(binbytes).to_vec(),
+        });
+        releases.builds.sort_by(|a, b| a.version.cmp(&b.version));
+    }
+}
+
+/// Generates release lists from a directory laid out like the releases, with the artifacts of
+/// each version in a subdirectory named after it, e.g. `1.1.2/ylem-linux-x86_64`. Other entries
+/// are ignored.
+pub fn releases_from_dir(dir: &Path) -> Result<GeneratedReleases, YlemVmError> {
+    let mut generated = GeneratedReleases::default();
+    for version_dir in sorted_entries(dir)? {
+        let name = version_dir
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        // installs download from `<releases-url>/<version>/<artifact>`, so e.g. `v1.1.2` won't do
+        let Ok(version) = Version::parse(&name) else {
+            continue;
+        };
+        if !version_dir.is_dir() {
+            continue;
+        }
+        for artifact in sorted_entries(&version_dir)? {
+            if artifact.is_file() {
+                let name = artifact.file_name().unwrap_or_default().to_string_lossy();
+                let binbytes = fs::read(&artifact).io_context("read", &artifact)?;
+                generated.add(&version, &name, &binbytes);
+            }
+        }
+    }
+    Ok(generated)
+}
+
+fn sorted_entries(dir: &Path) -> Result<Vec<PathBuf>, YlemVmError> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect()
+        })
+        .io_context("read directory", dir)?;
+    entries.sort();
+    Ok(entries)
+}
+
+/// Generates release lists from the assets of the release `tag` of the GitHub repository `repo`,
+/// e.g. `core-coin/ylem`. The tag must be the version, e.g. `1.1.2`, as installs download from
+/// `<releases-url>/<version>/<artifact>`. Assets that are not ylem binaries, e.g. checksum files,
+/// are skipped without being downloaded.
+pub async fn releases_from_github(
+    downloader: &dyn Downloader,
+    config: &Config,
+    repo: &str,
+    tag: &str,
+) -> Result<GeneratedReleases, YlemVmError> {
+    #[derive(Deserialize)]
+    struct Release {
+        assets: Vec<Asset>,
+    }
+    #[derive(Deserialize)]
+    struct Asset {
+        name: String,
+    }
+
+    let version =
+        Version::parse(tag).map_err(|_| YlemVmError::InvalidReleaseTag(tag.to_string()))?;
+    let release_url = Url::parse(&format!(
+        "https://api.github.com/repos/{repo}/releases/tags/{tag}"
+    ))?;
+    let release = downloader
+        .download(&release_url, config.request_timeout)
+        .await?;
+    let release: Release = serde_json::from_slice(&release)?;
+
+    let mut generated = GeneratedReleases::default();
+    for asset in release.assets {
+        if artifact_platform(&asset.name).is_none() {
+            generated.skipped.push(asset.name);
+            continue;
+        }
+        let url = github_download_url(repo, tag, &asset.name)?;
+        let binbytes = downloader.download(&url, config.request_timeout).await?;
+        generated.add(&version, &asset.name, &binbytes);
+    }
+    Ok(generated)
+}
+
+/// Base URL of the releases of a GitHub repository, to use as `releases-url`.
+pub fn github_releases_url(repo: &str) -> Result<Url, YlemVmError> {
+    Ok(Url::parse(&format!(
+        "https://github.com/{repo}/releases/download"
+    ))?)
+}
+
+/// Returns the GitHub repository of a `releases-url` like the one of
+/// [`github_releases_url`], e.g. `core-coin/ylem`.
+pub fn github_repo(releases_url: &Url) -> Option<String> {
+    if releases_url.host_str() != Some("github.com") {
+        return None;
+    }
+    let segments = releases_url.path_segments()?.collect::<Vec<_>>();
+    match segments[..] {
+        [owner, repo, "releases", "download"] | [owner, repo, "releases", "download", ""] => {
+            Some(format!("{owner}/{repo}"))
+        }
+        _ => None,
+    }
+}
+
+fn github_download_url(repo: &str, tag: &str, artifact: &str) -> Result<Url, YlemVmError> {
+    let base = github_releases_url(repo)?;
+    Ok(Url::parse(&format!("{base}/{tag}/{artifact}"))?)
+}
+
+/// Adds the builds of `new` to `existing`, replacing the ones of the same versions.
+pub fn merge_releases(existing: &mut Releases, new: &Releases) {
+    existing
+        .builds
+        .retain(|build| !new.releases.contains_key(&build.version));
+    existing.builds.extend(new.builds.iter().cloned());
+    existing.builds.sort_by(|a, b| a.version.cmp(&b.version));
+    existing.releases.extend(
+        new.releases
+            .iter()
+            .map(|(version, artifact)| (version.clone(), artifact.clone())),
+    );
+}
+
+/// Formats releases the way the bundled lists are formatted.
+pub fn format_releases<T: Serialize>(releases: &T) -> Result<String, YlemVmError> {
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    releases.serialize(&mut serializer)?;
+    out.push(b'\n');
+    Ok(String::from_utf8(out).expect("JSON is UTF-8"))
+}
+
+/// Returns the platform of a bundled release list from its file name, see
+/// [`releases::list_file_name`].
+pub fn list_platform(file_name: &str) -> Option<Platform> {
+    Platform::ALL
+        .into_iter()
+        .find(|platform| releases::list_file_name(*platform) == Some(file_name))
+}
+
+/// A problem of a release list found by [`validate_releases`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListProblem {
+    /// The version has more than one build.
+    DuplicateBuild(Version),
+    /// The version has more than one release artifact.
+    DuplicateRelease(Version),
+    /// The build of the version has no valid sha256 checksum.
+    MissingChecksum(Version),
+    /// The version has a release artifact but no build with its checksum.
+    ReleaseWithoutBuild(Version),
+    /// The version has a build but no release artifact.
+    BuildWithoutRelease(Version),
+    /// The release artifact of the version is built for another platform.
+    ForeignArtifact { version: Version, artifact: String },
+}
+
+impl fmt::Display for ListProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListProblem::DuplicateBuild(v) => write!(f, "{v} has more than one build"),
+            ListProblem::DuplicateRelease(v) => write!(f, "{v} has more than one release"),
+            ListProblem::MissingChecksum(v) => write!(f, "{v} has no valid sha256 checksum"),
+            ListProblem::ReleaseWithoutBuild(v) => write!(f, "{v} is released without a build"),
+            ListProblem::BuildWithoutRelease(v) => write!(f, "{v} has a build but no release"),
+            ListProblem::ForeignArtifact { version, artifact } => {
+                write!(
+                    f,
+                    "{version} releases {artifact}, which is for another platform"
+                )
+            }
+        }
+    }
+}
+
+/// Checks a release list in the JSON format of [`Releases`]. Artifacts are also checked against
+/// the platform of the list, if given.
+pub fn validate_releases(
+    json: &[u8],
+    platform: Option<Platform>,
+) -> Result<Vec<ListProblem>, YlemVmError> {
+    /// [`Releases`] keeping duplicate keys of the releases.
+    #[derive(Deserialize)]
+    struct RawReleases {
+        builds: Vec<BuildInfo>,
+        #[serde(deserialize_with = "entries")]
+        releases: Vec<(Version, String)>,
+    }
+
+    let list: RawReleases = serde_json::from_slice(json)?;
+    let mut problems = Vec::new();
+
+    let mut builds = BTreeMap::new();
+    for build in &list.builds {
+        if builds.insert(&build.version, build).is_some() {
+            problems.push(ListProblem::DuplicateBuild(build.version.clone()));
+        }
+        if build.sha256.len() != 32 {
+            problems.push(ListProblem::MissingChecksum(build.version.clone()));
+        }
+    }
+
+    let mut releases = BTreeMap::new();
+    for (version, artifact) in &list.releases {
+        if releases.insert(version, artifact).is_some() {
+            problems.push(ListProblem::DuplicateRelease(version.clone()));
+        }
+        if !builds.contains_key(version) {
+            problems.push(ListProblem::ReleaseWithoutBuild(version.clone()));
+        }
+        if platform.is_some() && artifact_platform(artifact) != platform {
+            problems.push(ListProblem::ForeignArtifact {
+                version: version.clone(),
+                artifact: artifact.clone(),
+            });
+        }
+    }
+    for version in builds.keys() {
+        if !releases.contains_key(version) {
+            problems.push(ListProblem::BuildWithoutRelease((*version).clone()));
+        }
+    }
+    Ok(problems)
+}
+
+/// Deserializes a map into its entries, keeping duplicate keys.
+fn entries<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(Version, String)>, D::Error> {
+    struct EntriesVisitor;
+
+    impl<'de> Visitor<'de> for EntriesVisitor {
+        type Value = Vec<(Version, String)>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a map of versions to artifacts")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(EntriesVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocalDownloader;
+
+    #[test]
+    fn maps_artifacts_to_platforms() {
+        for (artifact, platform) in [
+            ("ylem-linux-x86_64", Some(Platform::LinuxAmd64)),
+            ("ylem-linux-arm64", Some(Platform::LinuxAarch64)),
+            ("ylem-linux-x86_64-musl", Some(Platform::LinuxAmd64Musl)),
+            ("ylem-linux-aarch64-musl", Some(Platform::LinuxAarch64Musl)),
+            ("ylem-linux-riscv64", Some(Platform::LinuxRiscv64)),
+            ("ylem-darwin-x86_64", Some(Platform::MacOsAmd64)),
+            ("ylem-darwin-arm64", Some(Platform::MacOsAarch64)),
+            ("ylem-windows-x86_64", Some(Platform::WindowsAmd64)),
+            ("ylem-windows-x86_64.zip", Some(Platform::WindowsAmd64)),
+            ("ylem-windows-arm64.exe", Some(Platform::WindowsAarch64)),
+            ("ylem-freebsd-x86_64", Some(Platform::FreeBsdAmd64)),
+            ("ylem-darwin-x86_64-musl", None),
+            ("ylem-linux-x86_64.sha256", None),
+            ("checksums.txt", None),
+        ] {
+            assert_eq!(artifact_platform(artifact), platform, "{artifact}");
+        }
+        // the bundled lists name their artifacts this way
+        for platform in Platform::ALL {
+            for artifact in releases::all_releases(platform).unwrap().releases.values() {
+                assert_eq!(artifact_platform(artifact), Some(platform));
+            }
+        }
+    }
+
+    #[test]
+    fn generates_from_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        for (version, artifact) in [
+            ("1.1.2", "ylem-linux-x86_64"),
+            ("1.1.2", "ylem-darwin-arm64"),
+            ("1.1.2", "checksums.txt"),
+            ("1.2.0", "ylem-linux-x86_64"),
+            ("v1.3.0", "ylem-linux-x86_64"),
+        ] {
+            fs::create_dir_all(dir.path().join(version)).unwrap();
+            fs::write(dir.path().join(version).join(artifact), artifact).unwrap();
+        }
+        fs::write(dir.path().join("README.md"), "").unwrap();
+
+        let generated = releases_from_dir(dir.path()).unwrap();
+        assert_eq!(generated.skipped, vec!["checksums.txt".to_string()]);
+        assert_eq!(generated.releases.len(), Platform::ALL.len());
+        let linux = &generated.releases[&Platform::LinuxAmd64];
+        assert_eq!(
+            linux.clone().into_versions(),
+            vec![Version::new(1, 1, 2), Version::new(1, 2, 0)]
+        );
+        assert_eq!(
+            linux.get_checksum(&Version::new(1, 2, 0)).unwrap(),
+            sha2::Sha256::digest(b"ylem-linux-x86_64").to_vec()
+        );
+        assert!(generated.releases[&Platform::WindowsAmd64]
+            .releases
+            .is_empty());
+        for releases in generated.releases.values() {
+            let json = format_releases(releases).unwrap();
+            assert_eq!(validate_releases(json.as_bytes(), None).unwrap(), vec![]);
+        }
+
+        // the directory can be served as `releases-url`
+        let releases_url = Url::from_directory_path(dir.path()).unwrap();
+        for (version, artifact) in &linux.releases {
+            let url = releases::artifact_url(&releases_url, version, artifact).unwrap();
+            assert!(url.to_file_path().unwrap().is_file(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn generates_from_github() {
+        let mut downloader = LocalDownloader::new();
+        downloader.insert(
+            Url::parse("https://api.github.com/repos/acme/ylem/releases/tags/1.1.2").unwrap(),
+            r#"{"assets": [{"name": "ylem-linux-x86_64"}, {"name": "checksums.txt"}]}"#,
+        );
+        let download_url =
+            Url::parse("https://github.com/acme/ylem/releases/download/1.1.2/ylem-linux-x86_64")
+                .unwrap();
+        downloader.insert(download_url.clone(), "ylem");
+
+        let generated = releases_from_github(&downloader, &Config::default(), "acme/ylem", "1.1.2")
+            .await
+            .unwrap();
+        assert_eq!(generated.skipped, vec!["checksums.txt".to_string()]);
+        let linux = &generated.releases[&Platform::LinuxAmd64];
+        let version = Version::new(1, 1, 2);
+        assert_eq!(linux.get_artifact(&version).unwrap(), "ylem-linux-x86_64");
+
+        // installs from the written list download what was checksummed
+        let json = format_releases(linux).unwrap();
+        let written: Releases = serde_json::from_str(&json).unwrap();
+        let url = releases::artifact_url(
+            &github_releases_url("acme/ylem").unwrap(),
+            &version,
+            written.get_artifact(&version).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(url, download_url);
+
+        // a v prefix would put the builds at URLs installs don't use
+        let err = releases_from_github(&downloader, &Config::default(), "acme/ylem", "v1.1.2")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, YlemVmError::InvalidReleaseTag(tag) if tag == "v1.1.2"));
+    }
+
+    #[test]
+    fn finds_github_repo() {
+        assert_eq!(
+            github_repo(&Config::default().releases_url).as_deref(),
+            Some("core-coin/ylem")
+        );
+        assert_eq!(
+            github_repo(&github_releases_url("acme/ylem").unwrap()).as_deref(),
+            Some("acme/ylem")
+        );
+        assert_eq!(
+            github_repo(&Url::parse("https://mirror.example.com/releases").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn merges_releases() {
+        let mut existing = releases::all_releases(Platform::LinuxAmd64)
+            .unwrap()
+            .clone();
+        let mut generated = GeneratedReleases::default();
+        generated.add(&Version::new(1, 1, 2), "ylem-linux-x86_64", b"rebuilt");
+        generated.add(&Version::new(1, 0, 0), "ylem-linux-x86_64", b"old");
+        merge_releases(&mut existing, &generated.releases[&Platform::LinuxAmd64]);
+
+        assert_eq!(
+            existing
+                .builds
+                .iter()
+                .map(|b| b.version.to_string())
+                .collect::<Vec<_>>(),
+            vec!["1.0.0", "1.1.2"]
+        );
+        assert_eq!(
+            existing.get_checksum(&Version::new(1, 1, 2)).unwrap(),
+            sha2::Sha256::digest(b"rebuilt").to_vec()
+        );
+    }
+
+    #[test]
+    fn bundled_lists_are_valid() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("list");
+        for platform in Platform::ALL {
+            let file_name = releases::list_file_name(platform).unwrap();
+            assert_eq!(list_platform(file_name), Some(platform));
+            let json = fs::read(dir.join(file_name)).unwrap();
+            assert_eq!(validate_releases(&json, Some(platform)).unwrap(), vec![]);
+            // the generated format matches the bundled one
+            let releases: Releases = serde_json::from_slice(&json).unwrap();
+            assert_eq!(format_releases(&releases).unwrap().as_bytes(), json);
+        }
+    }
+
+    #[test]
+    fn finds_problems() {
+        let sha = format!("0x{}", "ab".repeat(32));
+        let json = format!(
+            r#"{{
+                "builds": [
+                    {{"version": "1.0.0", "sha256": "{sha}"}},
+                    {{"version": "1.0.0", "sha256": "{sha}"}},
+                    {{"version": "1.1.0", "sha256": ""}},
+                    {{"version": "1.2.0", "sha256": "{sha}"}}
+                ],
+                "releases": {{
+                    "1.0.0": "ylem-linux-x86_64",
+                    "1.0.0": "ylem-linux-x86_64",
+                    "1.1.0": "ylem-darwin-x86_64",
+                    "1.3.0": "ylem-linux-x86_64"
+                }}
+            }}"#
+        );
+        let v = |minor| Version::new(1, minor, 0);
+        assert_eq!(
+            validate_releases(json.as_bytes(), Some(Platform::LinuxAmd64)).unwrap(),
+            vec![
+                ListProblem::DuplicateBuild(v(0)),
+                ListProblem::MissingChecksum(v(1)),
+                ListProblem::DuplicateRelease(v(0)),
+                ListProblem::ForeignArtifact {
+                    version: v(1),
+                    artifact: "ylem-darwin-x86_64".to_string()
+                },
+                ListProblem::ReleaseWithoutBuild(v(3)),
+                ListProblem::BuildWithoutRelease(v(2)),
+            ]
+        );
+    }
+}
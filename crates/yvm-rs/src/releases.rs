@@ -7,9 +7,18 @@ use serde::{
 use std::{collections::BTreeMap, sync::Arc};
 use url::Url;
 
-use crate::{error::YlemVmError, platform::Platform};
+#[cfg(feature = "blocking")]
+use crate::download::BlockingReqwestDownloader;
+#[cfg(feature = "reqwest")]
+use crate::download::ReqwestDownloader;
+use crate::{
+    download::{BlockingDownloader, Downloader},
+    error::YlemVmError,
+    platform::Platform,
+    Config,
+};
 
-const YLEM_RELEASES_URL: &str = "https://github.com/core-coin/ylem/releases/download";
+pub(crate) const YLEM_RELEASES_URL: &str = "https://github.com/core-coin/ylem/releases/download";
 
 static YLEM_LINUX_AARCH_RELEASES: Lazy<Arc<Releases>> = Lazy::new(|| {
     Arc::new(
@@ -23,6 +32,21 @@ static YLEM_LINUX_AMD_RELEASES: Lazy<Releases> = Lazy::new(|| {
         .unwrap_or_else(|_| panic!("{}", parse_error_msg("Linux Amd")))
 });
 
+static YLEM_LINUX_AARCH_MUSL_RELEASES: Lazy<Releases> = Lazy::new(|| {
+    serde_json::from_str::<Releases>(include_str!("../list/LinuxAarchMuslList.json"))
+        .unwrap_or_else(|_| panic!("{}", parse_error_msg("Linux Aarch musl")))
+});
+
+static YLEM_LINUX_AMD_MUSL_RELEASES: Lazy<Releases> = Lazy::new(|| {
+    serde_json::from_str::<Releases>(include_str!("../list/LinuxAmdMuslList.json"))
+        .unwrap_or_else(|_| panic!("{}", parse_error_msg("Linux Amd musl")))
+});
+
+static YLEM_LINUX_RISCV_RELEASES: Lazy<Releases> = Lazy::new(|| {
+    serde_json::from_str::<Releases>(include_str!("../list/LinuxRiscvList.json"))
+        .unwrap_or_else(|_| panic!("{}", parse_error_msg("Linux Riscv")))
+});
+
 static YLEM_MAC_AMD_RELEASES: Lazy<Releases> = Lazy::new(|| {
     serde_json::from_str::<Releases>(include_str!("../list/MacAmdList.json"))
         .unwrap_or_else(|_| panic!("{}", parse_error_msg("Mac Amd")))
@@ -38,6 +62,16 @@ static YLEM_WINDOWS_RELEASES: Lazy<Releases> = Lazy::new(|| {
         .unwrap_or_else(|_| panic!("{}", parse_error_msg("Windows")))
 });
 
+static YLEM_WINDOWS_AARCH_RELEASES: Lazy<Releases> = Lazy::new(|| {
+    serde_json::from_str::<Releases>(include_str!("../list/WindowsAarchList.json"))
+        .unwrap_or_else(|_| panic!("{}", parse_error_msg("Windows Aarch")))
+});
+
+static YLEM_FREEBSD_AMD_RELEASES: Lazy<Releases> = Lazy::new(|| {
+    serde_json::from_str::<Releases>(include_str!("../list/FreeBsdAmdList.json"))
+        .unwrap_or_else(|_| panic!("{}", parse_error_msg("FreeBSD Amd")))
+});
+
 fn parse_error_msg(platform: &str) -> String {
     format!(
         "Failed to parse ylem releases for {}. Please contact maintainers",
@@ -101,7 +135,7 @@ mod hex_string {
         S: Serializer,
         T: AsRef<[u8]>,
     {
-        let value = hex::encode(value);
+        let value = format!("0x{}", hex::encode(value));
         serializer.serialize_str(&value)
     }
 }
@@ -110,23 +144,89 @@ mod hex_string {
 pub fn all_releases(platform: Platform) -> Result<&'static Releases, YlemVmError> {
     match platform {
         Platform::LinuxAarch64 => Ok(&*YLEM_LINUX_AARCH_RELEASES),
+        Platform::LinuxAarch64Musl => Ok(&*YLEM_LINUX_AARCH_MUSL_RELEASES),
         Platform::LinuxAmd64 => Ok(&*YLEM_LINUX_AMD_RELEASES),
+        Platform::LinuxAmd64Musl => Ok(&*YLEM_LINUX_AMD_MUSL_RELEASES),
+        Platform::LinuxRiscv64 => Ok(&*YLEM_LINUX_RISCV_RELEASES),
         Platform::MacOsAarch64 => Ok(&*YLEM_MAC_AARCH_RELEASES),
         Platform::MacOsAmd64 => Ok(&*YLEM_MAC_AMD_RELEASES),
         Platform::WindowsAmd64 => Ok(&*YLEM_WINDOWS_RELEASES),
+        Platform::WindowsAarch64 => Ok(&*YLEM_WINDOWS_AARCH_RELEASES),
+        Platform::FreeBsdAmd64 => Ok(&*YLEM_FREEBSD_AMD_RELEASES),
         Platform::Unsupported => Err(YlemVmError::UnsupportedOs(platform.to_string())),
     }
 }
 
-/// Construct the URL to the Ylem binary for the specified release version and target platform.
+/// Returns the platforms whose bundled releases include the version.
+pub fn platforms_with_version(version: &Version) -> Vec<Platform> {
+    Platform::ALL
+        .into_iter()
+        .filter(|platform| {
+            all_releases(*platform)
+                .map_or(false, |releases| releases.releases.contains_key(version))
+        })
+        .collect()
+}
+
+/// Name of the file in `list/` holding the bundled releases of the platform.
+pub fn list_file_name(platform: Platform) -> Option<&'static str> {
+    Some(match platform {
+        Platform::LinuxAarch64 => "LinuxAarchList.json",
+        Platform::LinuxAarch64Musl => "LinuxAarchMuslList.json",
+        Platform::LinuxAmd64 => "LinuxAmdList.json",
+        Platform::LinuxAmd64Musl => "LinuxAmdMuslList.json",
+        Platform::LinuxRiscv64 => "LinuxRiscvList.json",
+        Platform::MacOsAarch64 => "MacAarchList.json",
+        Platform::MacOsAmd64 => "MacAmdList.json",
+        Platform::WindowsAmd64 => "WindowsList.json",
+        Platform::WindowsAarch64 => "WindowsAarchList.json",
+        Platform::FreeBsdAmd64 => "FreeBsdAmdList.json",
+        Platform::Unsupported => return None,
+    })
+}
+
+/// Fetches a list of releases in the format of the bundled ones, e.g. from a mirror.
+#[cfg(feature = "reqwest")]
+pub async fn fetch_releases(url: Url, config: &Config) -> Result<Releases, YlemVmError> {
+    fetch_releases_with_downloader(&ReqwestDownloader::for_config(config)?, url, config).await
+}
+
+/// Same as [`fetch_releases`], downloading with the given [`Downloader`].
+pub async fn fetch_releases_with_downloader(
+    downloader: &dyn Downloader,
+    url: Url,
+    config: &Config,
+) -> Result<Releases, YlemVmError> {
+    let body = downloader.download(&url, config.request_timeout).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Blocking version of [`fetch_releases`]
+#[cfg(feature = "blocking")]
+pub fn blocking_fetch_releases(url: Url, config: &Config) -> Result<Releases, YlemVmError> {
+    let downloader = BlockingReqwestDownloader::for_config(config)?;
+    blocking_fetch_releases_with_downloader(&downloader, url, config)
+}
+
+/// Blocking version of [`fetch_releases_with_downloader`]
+pub fn blocking_fetch_releases_with_downloader(
+    downloader: &dyn BlockingDownloader,
+    url: Url,
+    config: &Config,
+) -> Result<Releases, YlemVmError> {
+    let body = downloader.download(&url, config.request_timeout)?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Construct the URL to the Ylem binary for the specified release version below the given base
+/// URL of the releases.
 pub fn artifact_url(
-    _platform: Platform,
+    releases_url: &Url,
     version: &Version,
     artifact: &str,
 ) -> Result<Url, YlemVmError> {
-    Ok(Url::parse(&format!(
-        "{YLEM_RELEASES_URL}/{version}/{artifact}"
-    ))?)
+    let base = releases_url.as_str().trim_end_matches('/');
+    Ok(Url::parse(&format!("{base}/{version}/{artifact}"))?)
 }
 
 #[cfg(test)]
@@ -139,7 +239,7 @@ mod tests {
             .expect("could not fetch releases for macos-aarch64");
         let rosetta = Version::new(1, 1, 2);
         let url1 = artifact_url(
-            Platform::MacOsAarch64,
+            &Url::parse(YLEM_RELEASES_URL).unwrap(),
             &rosetta,
             releases.get_artifact(&rosetta).unwrap(),
         )
@@ -168,6 +268,27 @@ mod tests {
         assert!(all_releases(Platform::LinuxAarch64).is_ok());
     }
 
+    #[tokio::test]
+    async fn test_all_releases_linux_musl() {
+        assert!(all_releases(Platform::LinuxAmd64Musl).is_ok());
+        assert!(all_releases(Platform::LinuxAarch64Musl).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_all_releases_linux_riscv64() {
+        assert!(all_releases(Platform::LinuxRiscv64).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_all_releases_windows_aarch64() {
+        assert!(all_releases(Platform::WindowsAarch64).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_all_releases_freebsd_amd64() {
+        assert!(all_releases(Platform::FreeBsdAmd64).is_ok());
+    }
+
     #[tokio::test]
     async fn releases_roundtrip() {
         let releases = all_releases(Platform::LinuxAmd64).unwrap();
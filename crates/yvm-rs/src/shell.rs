@@ -0,0 +1,344 @@
+use semver::Version;
+use std::fmt::Formatter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{env, fmt, fs, io};
+
+use crate::{error::IoContext, YlemVmError};
+
+/// Ends the line `yvm setup` adds to shell startup files, so that it can be found again.
+const SETUP_MARKER: &str = "# added by yvm setup";
+
+/// Hook shared by bash and zsh. Walks up from `$PWD` looking for a `.ylem-version` file and
+/// exports its content as `YLEM_VERSION`, unless the user overrode the version for this session.
+const POSIX_USE_ON_CD: &str = r#"__yvm_use_on_cd() {
+  local dir="$PWD" ylem_version=""
+  while :; do
+    if [ -f "$dir/.ylem-version" ]; then
+      IFS= read -r ylem_version < "$dir/.ylem-version" || true
+      break
+    fi
+    [ -z "$dir" ] && break
+    dir="${dir%/*}"
+  done
+  if [ -n "$ylem_version" ]; then
+    if [ -z "${YLEM_VERSION:-}" ] || [ "$YLEM_VERSION" = "${__YVM_CD_VERSION:-}" ]; then
+      export YLEM_VERSION="$ylem_version"
+      __YVM_CD_VERSION="$ylem_version"
+    fi
+  elif [ -n "${__YVM_CD_VERSION:-}" ] && [ "${YLEM_VERSION:-}" = "$__YVM_CD_VERSION" ]; then
+    unset YLEM_VERSION __YVM_CD_VERSION
+  fi
+}
+"#;
+
+const BASH_REGISTER_HOOK: &str = r#"case ";${PROMPT_COMMAND:-};" in
+  *";__yvm_use_on_cd;"*) ;;
+  *) PROMPT_COMMAND="__yvm_use_on_cd${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
+__yvm_use_on_cd
+"#;
+
+const ZSH_REGISTER_HOOK: &str = r#"autoload -U add-zsh-hook
+add-zsh-hook chpwd __yvm_use_on_cd
+__yvm_use_on_cd
+"#;
+
+const FISH_USE_ON_CD: &str = r#"function __yvm_use_on_cd --on-variable PWD
+    set -l dir $PWD
+    set -l ylem_version
+    while true
+        if test -f "$dir/.ylem-version"
+            read ylem_version < "$dir/.ylem-version"
+            break
+        end
+        test -z "$dir"; and break
+        set dir (string replace -r '/[^/]*$' '' -- $dir)
+    end
+    if test -n "$ylem_version"
+        if not set -q YLEM_VERSION; or test "$YLEM_VERSION" = "$__YVM_CD_VERSION"
+            set -gx YLEM_VERSION $ylem_version
+            set -g __YVM_CD_VERSION $ylem_version
+        end
+    else if set -q __YVM_CD_VERSION; and test "$YLEM_VERSION" = "$__YVM_CD_VERSION"
+        set -e YLEM_VERSION
+        set -e __YVM_CD_VERSION
+    end
+end
+__yvm_use_on_cd
+"#;
+
+/// Types of shells yvm can generate environment scripts for.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            s => Err(format!("unsupported shell {s}")),
+        }
+    }
+}
+
+impl Shell {
+    /// Detects the user's shell from the `SHELL` environment variable.
+    pub fn detect() -> Option<Shell> {
+        let shell = env::var_os("SHELL")?;
+        Path::new(&shell).file_name()?.to_str()?.parse().ok()
+    }
+
+    /// Returns the script that puts `bin_dir` in front of `PATH`. If `use_on_cd` is set, the
+    /// script also installs a hook that sets `YLEM_VERSION` from the nearest `.ylem-version`
+    /// file whenever the working directory changes.
+    pub fn env_script(&self, bin_dir: &Path, use_on_cd: bool) -> String {
+        let bin_dir = bin_dir.to_string_lossy();
+        let mut script = match self {
+            Shell::Bash | Shell::Zsh => {
+                format!("export PATH={}:\"$PATH\"\n", posix_quote(&bin_dir))
+            }
+            Shell::Fish => format!("set -gx PATH {} $PATH\n", fish_quote(&bin_dir)),
+        };
+        if use_on_cd {
+            match self {
+                Shell::Bash => {
+                    script.push_str(POSIX_USE_ON_CD);
+                    script.push_str(BASH_REGISTER_HOOK);
+                }
+                Shell::Zsh => {
+                    script.push_str(POSIX_USE_ON_CD);
+                    script.push_str(ZSH_REGISTER_HOOK);
+                }
+                Shell::Fish => script.push_str(FISH_USE_ON_CD),
+            }
+        }
+        script
+    }
+
+    /// Returns the startup file of the shell in the home directory `home`, which `yvm setup` adds
+    /// the environment to.
+    pub fn rc_file(&self, home: &Path) -> PathBuf {
+        match self {
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => env::var_os("ZDOTDIR")
+                .map_or_else(|| home.to_path_buf(), PathBuf::from)
+                .join(".zshrc"),
+            Shell::Fish => env::var_os("XDG_CONFIG_HOME")
+                .map_or_else(|| home.join(".config"), PathBuf::from)
+                .join("fish")
+                .join("config.fish"),
+        }
+    }
+
+    /// Returns the startup file line that evaluates the [`Shell::env_script`] printed by the
+    /// `yvm` binary at `yvm`.
+    pub fn setup_line(&self, yvm: &Path, use_on_cd: bool) -> String {
+        let yvm = yvm.to_string_lossy();
+        let flags = if use_on_cd { " --use-on-cd" } else { "" };
+        match self {
+            Shell::Bash | Shell::Zsh => format!(
+                "eval \"$({} env --shell {self}{flags})\" {SETUP_MARKER}",
+                posix_quote(&yvm)
+            ),
+            Shell::Fish => format!(
+                "{} env --shell fish{flags} | source {SETUP_MARKER}",
+                fish_quote(&yvm)
+            ),
+        }
+    }
+
+    /// Returns the commands that override the Ylem version for the current shell session.
+    pub fn use_version_script(&self, version: &Version) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("export YLEM_VERSION={version}\n"),
+            Shell::Fish => format!("set -gx YLEM_VERSION {version}\n"),
+        }
+    }
+
+    /// Returns the commands that drop the session override, falling back to the global version.
+    pub fn unset_version_script(&self) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => "unset YLEM_VERSION\n".to_string(),
+            Shell::Fish => "set -e YLEM_VERSION\n".to_string(),
+        }
+    }
+}
+
+/// Adds `line` to the shell startup file `rc_file`, replacing the line added by an earlier
+/// `yvm setup`. Returns whether the file changed.
+pub fn add_setup_line(rc_file: &Path, line: &str) -> Result<bool, YlemVmError> {
+    let content = read_rc_file(rc_file)?;
+    let mut lines = content
+        .lines()
+        .filter(|l| !l.ends_with(SETUP_MARKER))
+        .collect::<Vec<_>>();
+    lines.push(line);
+    let updated = lines.join("\n") + "\n";
+    if updated == content {
+        return Ok(false);
+    }
+    if let Some(dir) = rc_file.parent() {
+        fs::create_dir_all(dir).io_context("create directory", dir)?;
+    }
+    fs::write(rc_file, updated).io_context("write", rc_file)?;
+    Ok(true)
+}
+
+/// Removes the line added by `yvm setup` from the shell startup file `rc_file`. Returns whether
+/// the file changed.
+pub fn remove_setup_line(rc_file: &Path) -> Result<bool, YlemVmError> {
+    let content = read_rc_file(rc_file)?;
+    if !content.lines().any(|l| l.ends_with(SETUP_MARKER)) {
+        return Ok(false);
+    }
+    let lines = content
+        .lines()
+        .filter(|l| !l.ends_with(SETUP_MARKER))
+        .collect::<Vec<_>>();
+    let mut updated = lines.join("\n");
+    if !lines.is_empty() {
+        updated.push('\n');
+    }
+    fs::write(rc_file, updated).io_context("write", rc_file)?;
+    Ok(true)
+}
+
+fn read_rc_file(rc_file: &Path) -> Result<String, YlemVmError> {
+    match fs::read_to_string(rc_file) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        res => res.io_context("read", rc_file),
+    }
+}
+
+fn posix_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIN_DIR: &str = "/home/user/.yvm/bin";
+
+    #[test]
+    fn parse_shell() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            assert_eq!(shell.to_string().parse::<Shell>().unwrap(), shell);
+        }
+        assert!("tcsh".parse::<Shell>().is_err());
+    }
+
+    #[test]
+    fn quotes_paths() {
+        assert_eq!(posix_quote("/it's/bin"), r"'/it'\''s/bin'");
+        assert_eq!(fish_quote(r"/it's\bin"), r"'/it\'s\\bin'");
+    }
+
+    #[test]
+    fn bash_env() {
+        insta::assert_snapshot!(Shell::Bash.env_script(Path::new(BIN_DIR), false));
+    }
+
+    #[test]
+    fn bash_env_use_on_cd() {
+        insta::assert_snapshot!(Shell::Bash.env_script(Path::new(BIN_DIR), true));
+    }
+
+    #[test]
+    fn zsh_env() {
+        insta::assert_snapshot!(Shell::Zsh.env_script(Path::new(BIN_DIR), false));
+    }
+
+    #[test]
+    fn zsh_env_use_on_cd() {
+        insta::assert_snapshot!(Shell::Zsh.env_script(Path::new(BIN_DIR), true));
+    }
+
+    #[test]
+    fn fish_env() {
+        insta::assert_snapshot!(Shell::Fish.env_script(Path::new(BIN_DIR), false));
+    }
+
+    #[test]
+    fn fish_env_use_on_cd() {
+        insta::assert_snapshot!(Shell::Fish.env_script(Path::new(BIN_DIR), true));
+    }
+
+    #[test]
+    fn setup_lines() {
+        let yvm = Path::new("/home/user/.cargo/bin/yvm");
+        assert_eq!(
+            Shell::Bash.setup_line(yvm, false),
+            r#"eval "$('/home/user/.cargo/bin/yvm' env --shell bash)" # added by yvm setup"#
+        );
+        assert_eq!(
+            Shell::Fish.setup_line(yvm, true),
+            "'/home/user/.cargo/bin/yvm' env --shell fish --use-on-cd | source # added by yvm setup"
+        );
+    }
+
+    #[test]
+    fn edits_rc_files() {
+        let home = tempfile::tempdir().unwrap();
+        let rc_file = Shell::Bash.rc_file(home.path());
+        let yvm = Path::new("yvm");
+        fs::write(&rc_file, "alias ll='ls -l'").unwrap();
+
+        assert!(add_setup_line(&rc_file, &Shell::Bash.setup_line(yvm, false)).unwrap());
+        assert!(!add_setup_line(&rc_file, &Shell::Bash.setup_line(yvm, false)).unwrap());
+        // setting up again replaces the line
+        assert!(add_setup_line(&rc_file, &Shell::Bash.setup_line(yvm, true)).unwrap());
+        assert_eq!(
+            fs::read_to_string(&rc_file).unwrap(),
+            format!("alias ll='ls -l'\n{}\n", Shell::Bash.setup_line(yvm, true))
+        );
+
+        assert!(remove_setup_line(&rc_file).unwrap());
+        assert!(!remove_setup_line(&rc_file).unwrap());
+        assert_eq!(fs::read_to_string(&rc_file).unwrap(), "alias ll='ls -l'\n");
+
+        // missing startup files are created, including their directory
+        let config_fish = home.path().join(".config").join("fish").join("config.fish");
+        assert!(add_setup_line(&config_fish, &Shell::Fish.setup_line(yvm, false)).unwrap());
+        assert!(remove_setup_line(&config_fish).unwrap());
+        assert_eq!(fs::read_to_string(&config_fish).unwrap(), "");
+        assert!(!remove_setup_line(&home.path().join(".zshrc")).unwrap());
+    }
+
+    #[test]
+    fn session_override() {
+        let version = Version::new(1, 1, 2);
+        insta::assert_snapshot!([Shell::Bash, Shell::Zsh, Shell::Fish]
+            .iter()
+            .map(|shell| format!(
+                "# {shell}\n{}{}",
+                shell.use_version_script(&version),
+                shell.unset_version_script()
+            ))
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
+}
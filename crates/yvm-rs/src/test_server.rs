@@ -0,0 +1,145 @@
+//! Local HTTP server standing in for the GitHub releases in tests.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use url::Url;
+
+/// How the server answers requests for a path.
+#[derive(Clone, Debug)]
+pub(crate) enum Route {
+    /// Responds with the body.
+    Ok(Vec<u8>),
+    /// Responds with an empty body and the status code.
+    Status(u16),
+    /// Responds with the body after waiting.
+    Delayed(Duration, Vec<u8>),
+    /// Announces `length` bytes but closes the connection after sending the body.
+    Partial { body: Vec<u8>, length: usize },
+    /// Responds with the body if the request has the `Authorization` header, like a private
+    /// release, and with a 404 otherwise.
+    Authorized {
+        authorization: String,
+        body: Vec<u8>,
+    },
+}
+
+pub(crate) struct ReleaseServer {
+    addr: std::net::SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+    requests: Arc<AtomicUsize>,
+    url: Url,
+}
+
+impl ReleaseServer {
+    /// Starts serving the routes, keyed by path relative to [`ReleaseServer::url`], e.g.
+    /// `1.1.2/ylem-linux-x86_64` or `list.json`. Other paths respond with a 404.
+    pub(crate) fn start(routes: HashMap<String, Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let url = Url::parse(&format!("http://{addr}/releases/")).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let handle = {
+            let (stop, requests) = (stop.clone(), requests.clone());
+            let routes = Arc::new(routes);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    // answer concurrently, so slow routes don't hold up parallel requests
+                    let routes = routes.clone();
+                    thread::spawn(move || {
+                        let _ = respond(stream, &routes);
+                    });
+                }
+            })
+        };
+
+        Self {
+            addr,
+            stop,
+            handle: Some(handle),
+            requests,
+            url,
+        }
+    }
+
+    /// Base URL of the served releases.
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Number of requests received so far.
+    pub(crate) fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for ReleaseServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // wake up the accept loop
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Answers a single request and closes the connection.
+fn respond(mut stream: TcpStream, routes: &HashMap<String, Route>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut authorization = None;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+        line.clear();
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .trim_start_matches("/releases/");
+    let (status, body, length) = match routes.get(path).cloned() {
+        Some(Route::Ok(body)) => (200, body.clone(), body.len()),
+        Some(Route::Status(code)) => (code, vec![], 0),
+        Some(Route::Delayed(delay, body)) => {
+            thread::sleep(delay);
+            (200, body.clone(), body.len())
+        }
+        Some(Route::Partial { body, length }) => (200, body, length),
+        Some(Route::Authorized {
+            authorization: expected,
+            body,
+        }) if authorization.as_ref() == Some(&expected) => (200, body.clone(), body.len()),
+        Some(Route::Authorized { .. }) => (404, vec![], 0),
+        None => (404, vec![], 0),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} Fixture\r\nContent-Length: {length}\r\nConnection: close\r\n\r\n"
+    )?;
+    stream.write_all(&body)?;
+    stream.flush()
+}
@@ -0,0 +1,157 @@
+use semver::{Version, VersionReq};
+use std::{fmt, str::FromStr};
+
+use crate::{
+    all_releases, fallback_platform, installed_versions, platform, resolve_platform, Config,
+    YlemVmError,
+};
+
+/// A version requested by the user, e.g. `ylem +1.1 ...` on the command line of the `ylem` shim.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionSpec {
+    /// The most recent version.
+    Latest,
+    /// Exactly this version.
+    Exact(Version),
+    /// The most recent version matching the requirement. A partial version like `1.1` matches
+    /// every `1.1.x` version.
+    Req(VersionReq),
+}
+
+impl VersionSpec {
+    pub fn matches(&self, version: &Version) -> bool {
+        match self {
+            VersionSpec::Latest => true,
+            VersionSpec::Exact(v) => v == version,
+            VersionSpec::Req(req) => req.matches(version),
+        }
+    }
+
+    /// Returns the most recent of the versions matching the spec.
+    pub fn resolve<'a>(&self, versions: impl IntoIterator<Item = &'a Version>) -> Option<Version> {
+        versions
+            .into_iter()
+            .filter(|v| self.matches(v))
+            .max()
+            .cloned()
+    }
+}
+
+impl FromStr for VersionSpec {
+    type Err = YlemVmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "latest" {
+            return Ok(VersionSpec::Latest);
+        }
+        if let Ok(version) = Version::parse(s) {
+            return Ok(VersionSpec::Exact(version));
+        }
+        // a plain partial version means that exact minor or major version, not a caret range
+        let req = if s.starts_with(|c: char| c.is_ascii_digit()) {
+            VersionReq::parse(&format!("={s}"))?
+        } else {
+            VersionReq::parse(s)?
+        };
+        Ok(VersionSpec::Req(req))
+    }
+}
+
+impl fmt::Display for VersionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionSpec::Latest => f.write_str("latest"),
+            VersionSpec::Exact(version) => write!(f, "{version}"),
+            VersionSpec::Req(req) => write!(f, "{req}"),
+        }
+    }
+}
+
+/// Resolves a version spec to the most recent matching installed version. If `auto-install` is
+/// configured, versions that can be installed on this platform are considered too, so that e.g.
+/// `latest` is the most recent release. An exact version with builds for other platforms only
+/// fails with [`YlemVmError::UnsupportedVersion`].
+pub fn resolve_version_spec(spec: &VersionSpec, config: &Config) -> Result<Version, YlemVmError> {
+    let installed = installed_versions()?;
+    let platform = platform();
+    let mut available = Vec::new();
+    if config.auto_install {
+        for platform in [Some(platform), fallback_platform(platform, config)]
+            .into_iter()
+            .flatten()
+        {
+            available.extend(all_releases(platform)?.releases.keys().cloned());
+        }
+    }
+    resolve_in(spec, &installed, &available).map_err(|err| match spec {
+        VersionSpec::Exact(version) => match resolve_platform(platform, version, config) {
+            Err(unsupported @ YlemVmError::UnsupportedVersion { .. }) => unsupported,
+            _ => err,
+        },
+        _ => err,
+    })
+}
+
+fn resolve_in(
+    spec: &VersionSpec,
+    installed: &[Version],
+    available: &[Version],
+) -> Result<Version, YlemVmError> {
+    spec.resolve(installed.iter().chain(available))
+        .ok_or_else(|| YlemVmError::NoMatchingVersion {
+            spec: spec.to_string(),
+            installed: installed.to_vec(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_specs() {
+        for (spec, expected) in [
+            ("latest", VersionSpec::Latest),
+            ("1.1.2", VersionSpec::Exact(Version::new(1, 1, 2))),
+            ("1.1", VersionSpec::Req(VersionReq::parse("=1.1").unwrap())),
+            ("^1.0", VersionSpec::Req(VersionReq::parse("^1.0").unwrap())),
+        ] {
+            assert_eq!(spec.parse::<VersionSpec>().unwrap(), expected);
+        }
+        assert!("1.x.y".parse::<VersionSpec>().is_err());
+        assert!("".parse::<VersionSpec>().is_err());
+    }
+
+    #[test]
+    fn resolves_most_recent_match() {
+        let v = |s: &str| Version::parse(s).unwrap();
+        let installed = [v("1.0.3"), v("1.1.1"), v("1.1.2")];
+        let available = [v("1.1.3"), v("2.0.0")];
+        let resolve = |spec: &str, available: &[Version]| {
+            resolve_in(&spec.parse().unwrap(), &installed, available)
+        };
+
+        assert_eq!(resolve("latest", &[]).unwrap(), v("1.1.2"));
+        assert_eq!(resolve("latest", &available).unwrap(), v("2.0.0"));
+        assert_eq!(resolve("1.1", &[]).unwrap(), v("1.1.2"));
+        assert_eq!(resolve("1.1", &available).unwrap(), v("1.1.3"));
+        assert_eq!(resolve("1", &[]).unwrap(), v("1.1.2"));
+        assert_eq!(resolve("1.0.3", &available).unwrap(), v("1.0.3"));
+
+        let err = resolve("1.2", &available).unwrap_err();
+        assert!(
+            matches!(&err, YlemVmError::NoMatchingVersion { installed: i, .. } if i == &installed)
+        );
+        assert_eq!(
+            err.to_string(),
+            "No version matches =1.2, installed versions: 1.0.3, 1.1.1, 1.1.2"
+        );
+        assert_eq!(
+            resolve_in(&VersionSpec::Latest, &[], &[])
+                .unwrap_err()
+                .to_string(),
+            "No version matches latest, installed versions: none"
+        );
+    }
+}
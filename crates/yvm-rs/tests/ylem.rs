@@ -0,0 +1,131 @@
+//! Exit behavior of the `ylem` shim, running a fake compiler script in place of ylem.
+#![cfg(target_family = "unix")]
+
+use std::{
+    fs,
+    os::unix::{fs::PermissionsExt, process::ExitStatusExt},
+    process::{Command, Stdio},
+};
+use tempfile::TempDir;
+
+const VERSION: &str = "0.0.41";
+
+/// Returns a command running the shim with a data directory in which the compiler is `script`.
+fn shim(script: &str) -> (TempDir, Command) {
+    let home = tempfile::tempdir().unwrap();
+    install(&home, VERSION, script);
+
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_ylem"));
+    cmd.env("HOME", home.path()).env("YLEM_VERSION", VERSION);
+    (home, cmd)
+}
+
+/// Installs `script` as the compiler of `version` in the data directory.
+fn install(home: &TempDir, version: &str, script: &str) {
+    let version_dir = home.path().join(".yvm").join(version);
+    fs::create_dir_all(&version_dir).unwrap();
+    let ylem = version_dir.join(format!("ylem-{version}"));
+    fs::write(&ylem, format!("#!/bin/sh\n{script}\n")).unwrap();
+    fs::set_permissions(&ylem, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn exits_like_the_compiler() {
+    let (_home, mut cmd) = shim(r#"echo "$@"; exit 3"#);
+    let output = cmd.args(["--bin", "a b"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "--bin a b\n");
+
+    // the parent sees the signal instead of an exit code
+    let (_home, mut cmd) = shim("kill -TERM $$");
+    let status = cmd.status().unwrap();
+    assert_eq!(status.code(), None);
+    assert_eq!(status.signal(), Some(libc::SIGTERM));
+
+    // the compiler replaces the shim instead of running in a child process
+    let (_home, mut cmd) = shim("echo $$");
+    let child = cmd.stdout(Stdio::piped()).spawn().unwrap();
+    let pid = child.id();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        pid.to_string()
+    );
+}
+
+#[test]
+fn fails_without_the_compiler() {
+    let (home, mut cmd) = shim("exit 0");
+    fs::remove_dir_all(home.path().join(".yvm").join(VERSION)).unwrap();
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not installed"));
+}
+
+#[test]
+fn runs_the_version_after_plus() {
+    let (home, _) = shim(r#"echo 0.0.41 "$@""#);
+    install(&home, "0.0.42", r#"echo 0.0.42 "$@""#);
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_ylem"))
+            .env("HOME", home.path())
+            .env("YLEM_VERSION", VERSION)
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    // the spec overrides YLEM_VERSION and isn't passed on to the compiler
+    assert_eq!(run(&["--bin"]).1, "0.0.41 --bin\n");
+    assert_eq!(run(&["+0.0.42", "--bin"]).1, "0.0.42 --bin\n");
+    assert_eq!(run(&["+latest"]).1, "0.0.42\n");
+    assert_eq!(run(&["+0.0", "+x"]).1, "0.0.42 +x\n");
+
+    let (success, _, stderr) = run(&["+9.9.9"]);
+    assert!(!success);
+    assert!(
+        stderr.contains("No version matches 9.9.9, installed versions: 0.0.41, 0.0.42"),
+        "{stderr}"
+    );
+    let (success, _, stderr) = run(&["+nope"]);
+    assert!(!success);
+    assert!(stderr.contains("invalid version +nope"), "{stderr}");
+}
+
+#[test]
+fn launchers_record_use_for_prune() {
+    let home = tempfile::tempdir().unwrap();
+    let yvm = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_yvm"))
+            .env("HOME", home.path())
+            .env_remove("YLEM_VERSION")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+    };
+    let data_dir = home.path().join(".yvm");
+    for version in ["0.0.41", "0.0.42"] {
+        install(&home, version, &format!("echo {version}"));
+        // last run long ago
+        fs::write(data_dir.join(version).join(".last-used"), "1").unwrap();
+    }
+    yvm(&["rehash"]);
+
+    let output = Command::new(data_dir.join("bin").join("ylem-0.0.41"))
+        .env("HOME", home.path())
+        .env_remove("YLEM_VERSION")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0.0.41\n");
+
+    yvm(&["--yes", "prune", "--older-than", "1day"]);
+    assert!(data_dir.join("0.0.41").exists());
+    assert!(!data_dir.join("0.0.42").exists());
+}
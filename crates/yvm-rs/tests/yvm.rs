@@ -0,0 +1,228 @@
+//! Output of the `yvm` binary, run against a data directory in a temporary home directory.
+
+use std::{
+    fs,
+    io::{Read, Write},
+    net::TcpListener,
+    path::Path,
+    process::{Command, Output},
+};
+use tempfile::TempDir;
+
+/// Returns a command running `yvm` with a home directory of its own.
+fn yvm() -> (TempDir, Command) {
+    let home = tempfile::tempdir().unwrap();
+    let cmd = yvm_in(home.path());
+    (home, cmd)
+}
+
+/// Returns a command running `yvm` with the given home directory.
+fn yvm_in(home: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_yvm"));
+    cmd.env("HOME", home)
+        .env("YVM_NONINTERACTIVE", "1")
+        .env_remove("YLEM_VERSION");
+    cmd
+}
+
+/// Serves every request with the given status and body, returning the URL of the server.
+fn serve(status: &'static str, body: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            let head = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(body);
+        }
+    });
+    url
+}
+
+fn assert_exit_code(output: &Output, code: i32) {
+    assert_eq!(
+        output.status.code(),
+        Some(code),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn renders_io_errors_with_their_path() {
+    let (home, mut cmd) = yvm();
+    let global_version = home.path().join(".yvm").join(".global-version");
+    fs::create_dir_all(&global_version).unwrap();
+
+    let output = cmd.arg("list").output().unwrap();
+    assert_exit_code(&output, 11);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with(&format!(
+            "error: Could not read {}: ",
+            global_version.display()
+        )),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn lists_platforms_and_closest_versions_of_unsupported_versions() {
+    let (_home, mut cmd) = yvm();
+    let output = cmd
+        .args(["--platform", "linux-amd64-musl", "install", "1.1.2"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "Version: 1.1.2 unsupported on linux-amd64-musl\nAvailable for: linux-amd64, "
+        ),
+        "{stdout}"
+    );
+
+    let (_home, mut cmd) = yvm();
+    let output = cmd
+        .args(["--platform", "linux-amd64", "install", "1.0.0"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "Version: 1.0.0 unsupported on linux-amd64\nClosest versions for linux-amd64: 1.1.2"
+        ),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn exits_with_the_code_of_the_failure() {
+    for (args, code) in [
+        (&["list"][..], 0),
+        (&["install", "nope"], 2),
+        (&["install", "1.0.0"], 3),
+        (&["--platform", "linux-amd64-musl", "install", "1.1.2"], 4),
+        // builds that can't run here only go into a separate root
+        (&["--platform", "freebsd-amd64", "install", "1.1.2"], 2),
+        (&["remove", "1.1.2"], 5),
+        // prompts can't be answered without a terminal
+        (&["use", "1.1.2"], 2),
+        (&["config", "set", "no-such-key", "1"], 10),
+    ] {
+        let (_home, mut cmd) = yvm();
+        assert_exit_code(&cmd.args(args).output().unwrap(), code);
+    }
+
+    let (home, mut cmd) = yvm();
+    fs::create_dir_all(home.path().join(".yvm")).unwrap();
+    fs::write(home.path().join(".yvm").join(".layout-version"), "99").unwrap();
+    assert_exit_code(&cmd.arg("list").output().unwrap(), 12);
+}
+
+#[test]
+fn exits_with_the_code_of_failed_downloads() {
+    for (status, body, code) in [("404 Not Found", &b""[..], 7), ("200 OK", b"not ylem", 8)] {
+        let (_home, mut cmd) = yvm();
+        let output = cmd
+            .env("YVM_RELEASES_URL", serve(status, body))
+            .args(["--platform", "linux-amd64", "install", "1.1.2"])
+            .output()
+            .unwrap();
+        assert_exit_code(&output, code);
+    }
+}
+
+#[test]
+fn reinstalls_without_asking_when_non_interactive() {
+    let (home, mut cmd) = yvm();
+    assert_exit_code(&cmd.arg("list").output().unwrap(), 0);
+    let data_dir = home.path().join(".yvm");
+    fs::create_dir_all(data_dir.join("1.1.2")).unwrap();
+
+    for _ in 0..2 {
+        let output = yvm_in(home.path())
+            .args(["install", "1.1.2"])
+            .output()
+            .unwrap();
+        assert_exit_code(&output, 0);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("already installed"));
+    }
+    // the optional question took its default answer
+    assert_eq!(
+        fs::read_to_string(data_dir.join(".global-version")).unwrap(),
+        ""
+    );
+}
+
+#[test]
+fn switches_back_to_the_previous_global_version() {
+    let home = tempfile::tempdir().unwrap();
+    let data_dir = home.path().join(".yvm");
+    let run = |args: &[&str]| {
+        let output = yvm_in(home.path()).args(args).output().unwrap();
+        assert_exit_code(&output, 0);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    run(&["list"]);
+    for version in ["1.1.1", "1.1.2"] {
+        fs::create_dir_all(data_dir.join(version)).unwrap();
+    }
+
+    run(&["use", "1.1.1"]);
+    run(&["use", "1.1.2"]);
+    run(&["use", "-"]);
+    let global_version = || fs::read_to_string(data_dir.join(".global-version")).unwrap();
+    assert_eq!(global_version(), "1.1.1");
+    run(&["use", "-"]);
+    assert_eq!(global_version(), "1.1.2");
+
+    let history = run(&["history", "--json"]);
+    assert_eq!(history.lines().count(), 4);
+    assert!(
+        history
+            .lines()
+            .last()
+            .unwrap()
+            .contains(r#""operation":"use","version":"1.1.2","previous":"1.1.1"}"#),
+        "{history}"
+    );
+}
+
+#[test]
+fn prints_shell_scripts_despite_a_broken_setup() {
+    let (home, _) = yvm();
+    // a data directory of a newer yvm, and an invalid project config
+    let data_dir = home.path().join(".yvm");
+    fs::create_dir_all(&data_dir).unwrap();
+    fs::write(data_dir.join(".layout-version"), "99").unwrap();
+    fs::write(home.path().join(".yvm.toml"), "auto-install = maybe").unwrap();
+
+    for args in [
+        &["env", "--shell", "bash"][..],
+        &["completions", "zsh"],
+        &["__complete", "install"],
+        &["__complete", "use"],
+    ] {
+        let output = yvm_in(home.path())
+            .current_dir(home.path())
+            .args(args)
+            .output()
+            .unwrap();
+        assert_exit_code(&output, 0);
+        assert!(output.stderr.is_empty(), "{args:?}");
+    }
+
+    let output = yvm_in(home.path())
+        .current_dir(home.path())
+        .arg("list")
+        .output()
+        .unwrap();
+    assert_exit_code(&output, 12);
+}